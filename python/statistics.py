@@ -14,6 +14,7 @@ class PlotType(Enum):
     CONNECTIVITY = auto()
     CONNECTIONS = auto()
     FAILURES = auto()
+    CHURN = auto()
     RTT = auto()
     LATENCY_DISTANCE_RATIO = auto()
 
@@ -25,7 +26,7 @@ possible_plots = {
     },
     PlotType.CONNECTIVITY: {
         'title': 'Graph Connectivity',
-        'y_values': {'connected_components': 'Connected Components', 'articulation_points': 'Articulation Points'},
+        'y_values': {'connected_components': 'Connected Components', 'articulation_points': 'Articulation Points', 'bridges': 'Bridges'},
         'y_label': None,
     },
     PlotType.CONNECTIONS: {
@@ -38,18 +39,28 @@ possible_plots = {
         'y_values': {'failure_ratio': None},
         'y_label': 'Satellite Failures (%)',
     },
+    PlotType.CHURN: {
+        'title': 'Link Churn per Connection Update',
+        'y_values': {'links_added': 'Links Added', 'links_removed': 'Links Removed'},
+        'y_label': 'Links (units)',
+    },
     PlotType.RTT: {
-        'title': 'Round Trip Time (from London to Other Cities)',
-        'y_values': {'rtt_nyc': 'New York', 'rtt_singapore': 'Singapore', 'rtt_johannesburg': 'Johannesburg'},
+        'title': 'Round Trip Time (between City Pairs)',
+        'y_prefix': 'rtt_',
         'y_label': 'Round Trip Time (ms)',
     },
     PlotType.LATENCY_DISTANCE_RATIO: {
-        'title': 'Latency to Distance Ratio (from London to Other Cities)',
-        'y_values': {'latency_nyc': 'New York', 'latency_singapore': 'Singapore', 'latency_johannesburg': 'Johannesburg'},
+        'title': 'Latency to Distance Ratio (between City Pairs)',
+        'y_prefix': 'latency_',
         'y_label': 'Latency to Distance Ratio (s/m)',
     },
 }
 
+pair_labels = {'nyc': 'New York', 'singapore': 'Singapore', 'johannesburg': 'Johannesburg'}
+
+def pair_label(name: str) -> str:
+    return pair_labels.get(name, name.replace('_', ' ').title())
+
 def plot_line(ax: Axes, x: str, y: str, label: str) -> None:
     ax.plot(values.get(x, []), values.get(y, []), marker='.', label=label)
 
@@ -57,7 +68,11 @@ def plot(ax: Axes, p: PlotType) -> None:
     plot = possible_plots[p]
     title = plot['title']
     y_label = plot['y_label']
-    y_values = plot['y_values']
+    y_values = plot.get('y_values')
+    if y_values is None:
+        # One line per city pair, labeled with the name of the pair
+        prefix = plot['y_prefix']
+        y_values = {k: pair_label(k[len(prefix):]) for k in list(values) if k.startswith(prefix)}
 
     ax.clear()
     ax.set_title(title)
@@ -70,9 +85,9 @@ def plot(ax: Axes, p: PlotType) -> None:
         if label == None:
             legend = False
 
-    if y.startswith('rtt'):
+    if p == PlotType.RTT:
         ax.set_ylim(0, 200)
-    elif y.startswith('latency'):
+    elif p == PlotType.LATENCY_DISTANCE_RATIO:
         ax.set_ylim(0)
 
     if legend:
@@ -0,0 +1,375 @@
+use std::{ops::RangeInclusive, sync::mpsc::Sender};
+
+use toml::{Value, map::Map};
+
+use crate::{
+    contact_plan::ContactPlanConfig,
+    link_state::LinkStateConfig,
+    model::{EARTH_RADIUS, DEFAULT_LINK_CAPACITY, Simulation, Model, ConstellationType, CityPair, GroundSite, RegionPair, GeoCoordinates, TrafficDemand, Satellite},
+    packet_simulation::{PacketConfig, PacketFlow},
+    routing::{Gateway, Multipath, RoutingConfig},
+    strategy_registry::{StrategyConfig, StrategyRegistry},
+};
+
+/// Configuration used when no configuration file is specified.
+const DEFAULT_CONFIG: &str = "
+[constellation]
+altitude = 0.55e6
+num_orbital_planes = 12
+satellites_per_plane = 30
+inclination = 60.0
+max_connections = 4
+";
+
+/// Contents of a configuration file: the settings of a run (how long and how
+/// fast the simulation runs and where its statistics go) and the tables from
+/// which the simulation itself is built.
+pub struct Config {
+    pub file_path: Option<String>,
+    pub steps: Option<usize>,
+    pub update_frequency: f64,
+    pub update_frequency_server: f64,
+    pub threads: Option<usize>,
+    contents: Value,
+}
+
+impl Config {
+    /// Parses the contents of a configuration file.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let contents = contents.parse::<Value>().map_err(|e| format!("Error when parsing config file: {}", e))?;
+
+        if !matches!(contents.get("constellation"), Some(Value::Table(_))) {
+            return Err("Missing [constellation] table.".to_owned());
+        }
+
+        let empty_parameters = Map::new();
+        let simulation_parameters = match &contents.get("simulation") {
+            Some(Value::Table(t)) => t,
+            _ => &empty_parameters,
+        };
+
+        let file_path = simulation_parameters.get("file_path").and_then(Value::as_str).map(|s| s.to_owned());
+        let steps     = simulation_parameters.get("steps")    .and_then(Value::as_integer).map(|v| v as usize);
+
+        if file_path.is_some() && steps.is_none() {
+            return Err("Must specify a finite number of time steps when saving simulation data to a file!".to_owned());
+        }
+
+        let update_frequency        = simulation_parameters.get("update_frequency")       .and_then(Value::as_float).unwrap_or(10.0);
+        let update_frequency_server = simulation_parameters.get("update_frequency_server").and_then(Value::as_float).unwrap_or(update_frequency);
+        let threads                 = simulation_parameters.get("threads")                .and_then(Value::as_integer).map(|v| v as usize);
+
+        Ok(Config { file_path, steps, update_frequency, update_frequency_server, threads, contents })
+    }
+
+    /// Builds the simulation described by the configuration, selecting its
+    /// strategy from the given registry. Statistics are sent through the
+    /// given channel at every connection update.
+    pub fn build_simulation(&self, registry: &StrategyRegistry, statistics_channel: Sender<String>) -> Result<Simulation, String> {
+        let contents = &self.contents;
+        let constellation_parameters = match &contents["constellation"] {
+            Value::Table(t) => t,
+            _ => unreachable!(),
+        };
+
+        let empty_parameters = Map::new();
+        let simulation_parameters = match &contents.get("simulation") {
+            Some(Value::Table(t)) => t,
+            _ => &empty_parameters,
+        };
+
+        let orbiting_altitude    = constellation_parameters["altitude"]            .as_float()  .unwrap();
+        let num_orbital_planes   = constellation_parameters["num_orbital_planes"]  .as_integer().unwrap() as usize;
+        let satellites_per_plane = constellation_parameters["satellites_per_plane"].as_integer().unwrap() as usize;
+        let inclination          = constellation_parameters["inclination"]         .as_float()  .unwrap();
+        let max_connections      = constellation_parameters["max_connections"]     .as_integer().unwrap() as usize;
+        let link_capacity        = constellation_parameters.get("link_capacity").and_then(Value::as_float).unwrap_or(DEFAULT_LINK_CAPACITY);
+        let terminal_overrides: Vec<TerminalOverride> = match constellation_parameters.get("terminals") {
+            Some(Value::Array(overrides)) => overrides.iter().map(TerminalOverride::parse).collect(),
+            _ => Vec::new(),
+        };
+
+        let constellation_type   = constellation_parameters.get("type")   .and_then(Value::as_str)
+            .and_then(|v| ConstellationType::try_from(v).ok())
+            .unwrap_or(ConstellationType::Delta);
+        let phasing              = constellation_parameters.get("phasing").and_then(Value::as_integer).unwrap_or(0);
+        assert!((0..num_orbital_planes as i64).contains(&phasing));
+
+        let simulation_speed            = simulation_parameters.get("simulation_speed")           .and_then(Value::as_float).unwrap_or(1.0);
+        let connection_refresh_interval = simulation_parameters.get("connection_refresh_interval").and_then(Value::as_float).unwrap_or(10.0);
+
+        let rng_seed                      = simulation_parameters.get("rng_seed")                     .and_then(Value::as_integer).map(|v| v as u64);
+        let starting_failure_probability  = simulation_parameters.get("starting_failure_probability") .and_then(Value::as_float).unwrap_or(0.0);
+        let recurrent_failure_probability = simulation_parameters.get("recurrent_failure_probability").and_then(Value::as_float).unwrap_or(0.0);
+        assert!((0.0..=1.0).contains(&recurrent_failure_probability));
+        assert!((0.0..=1.0).contains(&starting_failure_probability));
+
+        let city_pairs: Vec<CityPair> = match &contents.get("city_pairs") {
+            Some(Value::Array(pairs)) => pairs.iter().map(parse_city_pair).collect(),
+            _ => CityPair::defaults(),
+        };
+
+        let region_pairs = match &contents.get("region_pairs") {
+            Some(Value::Array(pairs)) => pairs.iter().map(parse_region_pair).collect(),
+            _ => Vec::new(),
+        };
+
+        let ground_sites = match &contents.get("ground_sites") {
+            Some(Value::Array(sites)) => sites.iter().map(parse_ground_site).collect(),
+            _ => Vec::new(),
+        };
+
+        let link_state_config = match contents.get("routing").and_then(|r| r.get("link_state")) {
+            Some(Value::Table(params)) => {
+                let detection_delay  = params.get("detection_delay") .and_then(Value::as_float).unwrap_or(0.05);
+                let processing_delay = params.get("processing_delay").and_then(Value::as_float).unwrap_or(0.001);
+                let spf_delay        = params.get("spf_delay")       .and_then(Value::as_float).unwrap_or(0.05);
+                Some(LinkStateConfig::new(detection_delay, processing_delay, spf_delay))
+            },
+            _ => None,
+        };
+
+        let routing_config = match &contents.get("routing") {
+            Some(Value::Table(params)) => {
+                let gateways = match params.get("gateways") {
+                    Some(Value::Array(gateways)) => gateways.iter().enumerate().map(|(i, g)| parse_gateway(i, g)).collect(),
+                    _ => Vec::new(),
+                };
+                let backhaul_latency = params.get("backhaul_latency").and_then(Value::as_float)  .unwrap_or(0.01);
+                let ecmp_tolerance   = params.get("ecmp_tolerance")  .and_then(Value::as_float)  .unwrap_or(0.0);
+                let multipath_routes = params.get("multipath_routes").and_then(Value::as_integer).unwrap_or(4) as usize;
+                let multipath = match params.get("multipath").and_then(Value::as_str).unwrap_or("shortest_path") {
+                    "shortest_path" => Multipath::ShortestPath,
+                    "ecmp" => Multipath::Ecmp(ecmp_tolerance),
+                    "weighted" => Multipath::Weighted(multipath_routes),
+                    other => return Err(format!("Invalid multipath mode \"{}\".", other)),
+                };
+                RoutingConfig::new(gateways, backhaul_latency).with_multipath(multipath)
+            },
+            _ => RoutingConfig::default(),
+        };
+
+        let packet_config = match &contents.get("packets") {
+            Some(Value::Table(params)) => {
+                let flows = match params.get("flows") {
+                    Some(Value::Array(flows)) => flows.iter().map(parse_packet_flow).collect(),
+                    _ => Vec::new(),
+                };
+                let packet_size = params.get("packet_size").and_then(Value::as_float)  .unwrap_or(1500.0);
+                let queue_size  = params.get("queue_size") .and_then(Value::as_integer).unwrap_or(100) as usize;
+                Some(PacketConfig::new(flows, packet_size, queue_size))
+            },
+            _ => None,
+        };
+
+        let traffic_demands: Vec<TrafficDemand> = match &contents.get("traffic_demands") {
+            Some(Value::Array(demands)) => demands.iter().map(parse_traffic_demand).collect(),
+            _ => Vec::new(),
+        };
+
+        // Without a [strategy] table, the default strategy of the registry is used
+        let strategy_parameters = match contents.get("strategy") {
+            Some(Value::Table(params)) => params.clone(),
+            Some(_) => return Err("Strategy must be a table.".to_owned()),
+            None => Map::new(),
+        };
+        let strategy_config = StrategyConfig { city_pairs: &city_pairs, traffic_demands: &traffic_demands, rng_seed };
+        let strategy = registry.build(&strategy_parameters, &strategy_config)?;
+
+        // Future topologies are predicted by separate instances of the strategy
+        let contact_plan_config = match &contents.get("contact_plan") {
+            Some(Value::Table(params)) => {
+                let registry = registry.clone();
+                let city_pairs = city_pairs.clone();
+                let traffic_demands = traffic_demands.clone();
+                Some(ContactPlanConfig {
+                    horizon:      params.get("horizon") .and_then(Value::as_float).unwrap_or(600.0),
+                    interval:     params.get("interval").and_then(Value::as_float).unwrap_or(connection_refresh_interval),
+                    new_strategy: Box::new(move || {
+                        let config = StrategyConfig { city_pairs: &city_pairs, traffic_demands: &traffic_demands, rng_seed };
+                        registry.build(&strategy_parameters, &config).expect("Strategy was already built from these parameters")
+                    }),
+                })
+            },
+            _ => None,
+        };
+
+        let mut model = Model::new(
+            num_orbital_planes,
+            satellites_per_plane,
+            inclination.to_radians(),
+            constellation_type,
+            phasing as usize,
+            EARTH_RADIUS + orbiting_altitude,
+            max_connections,
+        );
+
+        for sat in model.satellites_mut() {
+            sat.set_link_capacity(link_capacity);
+        }
+
+        // Later overrides take precedence over earlier ones
+        for terminal_override in &terminal_overrides {
+            for sat in model.satellites_mut() {
+                if terminal_override.applies_to(sat) {
+                    if let Some(max_connections) = terminal_override.max_connections {
+                        sat.set_max_connections(max_connections);
+                    }
+                    if let Some(link_capacity) = terminal_override.link_capacity {
+                        sat.set_link_capacity(link_capacity);
+                    }
+                }
+            }
+        }
+
+        Ok(Simulation::new(
+            model,
+            simulation_speed / self.update_frequency,
+            simulation_speed,
+            connection_refresh_interval,
+            rng_seed,
+            starting_failure_probability,
+            recurrent_failure_probability,
+            city_pairs,
+            ground_sites,
+            traffic_demands,
+            region_pairs,
+            routing_config,
+            link_state_config,
+            contact_plan_config,
+            packet_config,
+            strategy,
+            statistics_channel,
+        ))
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::parse(DEFAULT_CONFIG).unwrap()
+    }
+}
+
+/// Number of terminals and/or their data rate for the satellites in a range
+/// of orbital planes and/or a range of satellite ids (both inclusive).
+struct TerminalOverride {
+    planes: Option<RangeInclusive<usize>>,
+    satellites: Option<RangeInclusive<usize>>,
+    max_connections: Option<usize>,
+    link_capacity: Option<f64>,
+}
+
+impl TerminalOverride {
+    fn parse(value: &Value) -> Self {
+        let parse_range = |key: &str| value.get(key).map(|range| {
+            let bounds = range.as_array().expect("Ranges must be [first, last] arrays!");
+            assert_eq!(bounds.len(), 2);
+            bounds[0].as_integer().unwrap() as usize..=bounds[1].as_integer().unwrap() as usize
+        });
+
+        TerminalOverride {
+            planes: parse_range("planes"),
+            satellites: parse_range("satellites"),
+            max_connections: value.get("max_connections").and_then(Value::as_integer).map(|v| v as usize),
+            link_capacity: value.get("link_capacity").and_then(Value::as_float),
+        }
+    }
+
+    fn applies_to(&self, sat: &Satellite) -> bool {
+        let in_planes = self.planes.as_ref().is_none_or(|planes| planes.contains(&sat.orbital_plane().id()));
+        let in_satellites = self.satellites.as_ref().is_none_or(|satellites| satellites.contains(&sat.id()));
+
+        in_planes && in_satellites
+    }
+}
+
+fn parse_coordinates(value: &Value) -> GeoCoordinates {
+    let coordinates = value.as_array().expect("Coordinates must be a [latitude, longitude] array!");
+    assert_eq!(coordinates.len(), 2);
+
+    GeoCoordinates::new(coordinates[0].as_float().unwrap(), coordinates[1].as_float().unwrap())
+}
+
+fn parse_city_pair(value: &Value) -> CityPair {
+    CityPair::new(
+        value["name"].as_str().unwrap(),
+        parse_coordinates(&value["source"]),
+        parse_coordinates(&value["destination"]),
+    )
+}
+
+fn parse_region_pair(value: &Value) -> RegionPair {
+    let parse_stations = |key: &str| value[key].as_array().expect("Regions must be arrays of coordinates!")
+        .iter().map(parse_coordinates).collect();
+
+    RegionPair::new(value["name"].as_str().unwrap(), parse_stations("sources"), parse_stations("destinations"))
+}
+
+fn parse_ground_site(value: &Value) -> GroundSite {
+    GroundSite::new(value["name"].as_str().unwrap(), parse_coordinates(&value["coordinates"]))
+}
+
+fn parse_gateway(index: usize, value: &Value) -> Gateway {
+    let name = value.get("name").and_then(Value::as_str).map(str::to_owned).unwrap_or_else(|| format!("gateway_{}", index));
+    Gateway::new(&name, parse_coordinates(&value["coordinates"]))
+}
+
+fn parse_traffic_demand(value: &Value) -> TrafficDemand {
+    TrafficDemand::new(
+        parse_coordinates(&value["source"]),
+        parse_coordinates(&value["destination"]),
+        value.get("volume").and_then(Value::as_float).unwrap_or(1.0),
+    )
+}
+
+fn parse_packet_flow(value: &Value) -> PacketFlow {
+    PacketFlow::new(
+        parse_coordinates(&value["source"]),
+        parse_coordinates(&value["destination"]),
+        value.get("rate").and_then(Value::as_float).unwrap_or(1000.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use petgraph::graphmap::GraphMap;
+
+    use super::*;
+    use crate::connection_strategy::ConnectionStrategy;
+
+    /// Strategy that leaves every satellite without links.
+    struct EmptyStrategy;
+
+    impl ConnectionStrategy for EmptyStrategy {
+        fn run(&mut self, _model: &Model) -> crate::model::ConnectionGraph {
+            GraphMap::new()
+        }
+    }
+
+    const CONFIG: &str = "
+[constellation]
+altitude = 0.55e6
+num_orbital_planes = 6
+satellites_per_plane = 10
+inclination = 53.0
+max_connections = 4
+";
+
+    #[test]
+    fn registered_strategies_are_selected_from_configuration() {
+        let mut registry = StrategyRegistry::default();
+        registry.register("empty", |_, _, _| Ok(Box::new(EmptyStrategy)));
+        let (sender, _receiver) = mpsc::channel();
+
+        let config = Config::parse(&format!("{}\n[strategy]\ntype = \"empty\"", CONFIG)).unwrap();
+        assert_eq!(config.build_simulation(&registry, sender.clone()).unwrap().topology().edge_count(), 0);
+
+        let config = Config::parse(CONFIG).unwrap();
+        assert!(config.build_simulation(&registry, sender.clone()).unwrap().topology().edge_count() > 0);
+
+        let config = Config::parse(&format!("{}\n[strategy]\ntype = \"empty\"", CONFIG)).unwrap();
+        assert!(config.build_simulation(&StrategyRegistry::default(), sender).is_err());
+    }
+}
@@ -1,18 +1,43 @@
 
+use std::{iter, time::{Duration, Instant}};
+
 use kiddo::{KdTree, distance::squared_euclidean};
-use petgraph::graphmap::GraphMap;
-use crate::model::{Model, ConnectionGraph};
+use petgraph::{graphmap::GraphMap, Undirected};
+use rand::{Rng, rngs::StdRng, SeedableRng, seq::SliceRandom};
+use rayon::prelude::*;
+use crate::{
+    flow_model::{FlowAssignment, VertexDisjointPaths},
+    model::{Model, ConnectionGraph, CityPair, TrafficDemand, has_line_of_sight},
+    routing::{self, Route, RoutingConfig},
+    statistics::critical_elements,
+};
 
 fn is_edge_valid(topology: &ConnectionGraph, model: &Model, a: usize, b: usize) -> bool {
     let sat_a = &model.satellites()[a];
     let sat_b = &model.satellites()[b];
 
-    let pos_b = sat_b.position();
-
     let both_alive = sat_a.status() && sat_b.status();
-    let connections_available = topology.edges(a).count() < model.max_connections() && topology.edges(b).count() < model.max_connections();
+    let connections_available = has_free_terminal(topology, model, a) && has_free_terminal(topology, model, b);
+
+    both_alive && connections_available && can_link(model, a, b)
+}
+
+/// Whether two different satellites are in line of sight and allowed to be
+/// linked.
+fn can_link(model: &Model, a: usize, b: usize) -> bool {
+    let satellites = model.satellites();
+    a != b && model.is_link_allowed(a, b) && satellites[a].has_line_of_sight(satellites[b].position())
+}
+
+fn has_free_terminal(topology: &ConnectionGraph, model: &Model, a: usize) -> bool {
+    topology.edges(a).count() < model.satellites()[a].max_connections()
+}
 
-    both_alive && connections_available && sat_a.has_line_of_sight(pos_b)
+fn link_length(model: &Model, a: usize, b: usize) -> f64 {
+    let pos_a = model.satellites()[a].position();
+    let pos_b = model.satellites()[b].position();
+
+    (pos_a - pos_b).norm()
 }
 
 fn add_edge(topology: &mut ConnectionGraph, model: &Model, a: usize, b: usize) {
@@ -20,26 +45,201 @@ fn add_edge(topology: &mut ConnectionGraph, model: &Model, a: usize, b: usize) {
         return;
     }
 
-    let pos_a = model.satellites()[a].position();
-    let pos_b = model.satellites()[b].position();
+    topology.add_edge(a, b, model.link(a, b));
+}
+
+/// Adds candidate links in order, like `add_edge`. The line of sight between
+/// the satellites of every candidate, which does not depend on the links
+/// added before it, is checked in parallel beforehand.
+fn add_edges(topology: &mut ConnectionGraph, model: &Model, candidates: &[(usize, usize)]) {
+    let satellites = model.satellites();
+    let visible: Vec<bool> = candidates.par_iter()
+        .map(|&(a, b)| can_link(model, a, b))
+        .collect();
+
+    for (&(a, b), visible) in candidates.iter().zip(visible) {
+        let available = satellites[a].status() && satellites[b].status()
+            && has_free_terminal(topology, model, a) && has_free_terminal(topology, model, b);
 
-    let length = (pos_a - pos_b).norm();
+        if visible && available {
+            topology.add_edge(a, b, model.link(a, b));
+        }
+    }
+}
+
+/// Number of samples used to estimate the lifetime of a link.
+const LIFETIME_SAMPLES: usize = 60;
+
+/// Estimates how long (in seconds, up to `horizon`) the link between two
+/// satellites will remain unobstructed by the Earth, by propagating their
+/// orbits forward from the current simulation time.
+fn link_lifetime(model: &Model, a: usize, b: usize, horizon: f64) -> f64 {
+    let sat_a = &model.satellites()[a];
+    let sat_b = &model.satellites()[b];
+    let step = horizon / LIFETIME_SAMPLES as f64;
+
+    for i in 1..=LIFETIME_SAMPLES {
+        let t = model.t() + i as f64 * step;
+        if !has_line_of_sight(&sat_a.position_at(t), &sat_b.position_at(t)) {
+            return (i - 1) as f64 * step;
+        }
+    }
 
-    topology.add_edge(a, b, length);
+    horizon
 }
 
+/// Returns the links between each active satellite and its `candidates`
+/// nearest active neighbors in line of sight, as (a, b) pairs with a < b.
+/// The k-d tree is rebuilt with the current satellite positions.
+fn candidate_links(kd_tree: &mut KdTree<f64, usize, 3>, model: &Model, candidates: usize) -> Vec<(usize, usize)> {
+    *kd_tree = KdTree::new();
+    for sat in model.satellites().iter().filter(|s| s.status()) {
+        let _ = kd_tree.add(sat.position().as_slice().try_into().unwrap(), sat.id());
+    }
+
+    let mut links = Vec::new();
+    for sat in model.satellites().iter().filter(|s| s.status()) {
+        let pos = sat.position().as_slice().try_into().unwrap();
+        let neighbors = kd_tree.iter_nearest(pos, &squared_euclidean).unwrap()
+            .map(|(_, &other)| other)
+            .filter(|&other| other != sat.id() && can_link(model, sat.id(), other))
+            .take(candidates);
+
+        for other in neighbors {
+            links.push((sat.id().min(other), sat.id().max(other)));
+        }
+    }
+
+    // Links that are candidates for both of their satellites appear twice
+    links.sort_unstable();
+    links.dedup();
+
+    links
+}
+
+/// Returns the longest link of a satellite in the given topology, as a
+/// (neighbor, length) pair.
+fn longest_link(topology: &ConnectionGraph, a: usize) -> Option<(usize, f64)> {
+    topology.edges(a)
+        .map(|(_, b, link)| (b, link.length))
+        .max_by(|(_, l1), (_, l2)| l1.partial_cmp(l2).unwrap())
+}
+
+/// State of the simulation at the time of a connection update, beyond the
+/// satellite positions that are available through the model.
+pub struct StrategyContext<'a> {
+    /// Topology established in the previous connection update (without the
+    /// satellites that have failed since then).
+    pub previous: &'a ConnectionGraph,
+    /// Current simulation time, in seconds.
+    pub t: f64,
+    /// Time elapsed since the previous connection update, in seconds.
+    pub elapsed: f64,
+    /// Satellites that have failed since the previous connection update.
+    pub failures: &'a [usize],
+}
+
+/// Set of links to add to and remove from the previous topology.
+#[derive(Default)]
+pub struct TopologyDiff {
+    pub added: Vec<(usize, usize)>,
+    pub removed: Vec<(usize, usize)>,
+}
+
+impl TopologyDiff {
+    /// Applies the diff to the previous topology, removing failed satellites
+    /// and updating link lengths according to the current satellite positions.
+    pub fn apply(&self, previous: &ConnectionGraph, model: &Model) -> ConnectionGraph {
+        let mut topology = previous.clone();
+
+        for sat in model.satellites() {
+            if sat.status() {
+                topology.add_node(sat.id());
+            }
+            else {
+                topology.remove_node(sat.id());
+            }
+        }
+
+        for &(a, b) in &self.removed {
+            topology.remove_edge(a, b);
+        }
+
+        for &(a, b) in &self.added {
+            if topology.contains_node(a) && topology.contains_node(b) {
+                topology.add_edge(a, b, model.link(a, b));
+            }
+        }
+
+        for (a, b, link) in topology.all_edges_mut() {
+            *link = model.link(a, b);
+        }
+
+        topology
+    }
+}
+
+pub enum TopologyUpdate {
+    Full(ConnectionGraph),
+    Diff(TopologyDiff),
+}
+
+/// Strategy used to establish links between satellites. Implementors build
+/// the topology from scratch in [`ConnectionStrategy::run`], and strategies
+/// that depend on the previous topology or on failure events also override
+/// [`ConnectionStrategy::update`] (implementing `run` with
+/// [`initial_topology`]).
 pub trait ConnectionStrategy: Send {
     fn run(&mut self, model: &Model) -> ConnectionGraph;
+
+    fn update(&mut self, model: &Model, _context: &StrategyContext) -> TopologyUpdate {
+        TopologyUpdate::Full(self.run(model))
+    }
+}
+
+/// Topology established by an update of a strategy when there is no previous
+/// topology (i.e. at the first connection update).
+pub fn initial_topology(strategy: &mut impl ConnectionStrategy, model: &Model) -> ConnectionGraph {
+    let previous = GraphMap::new();
+    let context = StrategyContext {
+        previous: &previous,
+        t: model.t(),
+        elapsed: 0.0,
+        failures: &[],
+    };
+
+    match strategy.update(model, &context) {
+        TopologyUpdate::Full(topology) => topology,
+        TopologyUpdate::Diff(diff) => diff.apply(&previous, model),
+    }
 }
 
 pub struct GridStrategy {
     offset: usize,
+    /// Cross-plane links are not established when either satellite is above
+    /// this latitude (in degrees, north or south).
+    max_cross_plane_latitude: Option<f64>,
+    /// Whether cross-plane links are established across the seam of
+    /// constellations with counter-rotating planes.
+    seam_links: bool,
 }
 
 impl GridStrategy {
-    pub fn new(offset: usize) -> Self {
+    pub fn new(offset: usize, max_cross_plane_latitude: Option<f64>, seam_links: bool) -> Self {
         GridStrategy {
             offset,
+            max_cross_plane_latitude,
+            seam_links,
+        }
+    }
+
+    fn allows_cross_plane_link(&self, model: &Model, a: usize, b: usize) -> bool {
+        match self.max_cross_plane_latitude {
+            Some(max_latitude) => {
+                let satellites = model.satellites();
+                satellites[a].latitude().abs() <= max_latitude && satellites[b].latitude().abs() <= max_latitude
+            }
+            None => true,
         }
     }
 }
@@ -55,41 +255,62 @@ impl ConnectionStrategy for GridStrategy {
         let num_planes = model.orbital_planes().len();
         let sats_per_plane = num_sats / num_planes;
 
+        let mut candidates = Vec::new();
         for plane in 0..num_planes {
             let start = plane * sats_per_plane;
             for sat in 0..sats_per_plane {
-                add_edge(
-                    &mut topology,
-                    model,
-                    start + sat,
-                    start + (sat + 1) % sats_per_plane
-                );
+                candidates.push((start + sat, start + (sat + 1) % sats_per_plane));
             }
         }
 
+        let has_seam = model.constellation_type().has_seam();
+
         for sat in 0..sats_per_plane {
             for plane in 0..num_planes {
-                add_edge(
-                    &mut topology,
-                    model,
-                    plane * sats_per_plane + sat,
-                    ((plane + 1) % num_planes) * sats_per_plane + (sat + self.offset) % sats_per_plane
-                );
+                // The last plane of a constellation with a seam is adjacent to
+                // the first one, but its satellites move in the opposite direction
+                if has_seam && !self.seam_links && plane == num_planes - 1 {
+                    continue;
+                }
+
+                let a = plane * sats_per_plane + sat;
+                let b = ((plane + 1) % num_planes) * sats_per_plane + (sat + self.offset) % sats_per_plane;
+
+                if self.allows_cross_plane_link(model, a, b) {
+                    candidates.push((a, b));
+                }
             }
         }
 
+        add_edges(&mut topology, model, &candidates);
+
         topology
     }
 }
 
+/// Number of nearest satellites whose line of sight is checked in parallel
+/// for each satellite, which is enough to fill the terminals of most of them.
+const NEAREST_CANDIDATES: usize = 16;
+
 pub struct NearestNeighborStrategy {
     kd_tree: KdTree<f64, usize, 3>,
+    /// Whether links are only established between satellites in different
+    /// orbital planes.
+    cross_plane_only: bool,
 }
 
 impl NearestNeighborStrategy {
     pub fn new() -> Self {
         NearestNeighborStrategy {
             kd_tree: KdTree::new(),
+            cross_plane_only: false,
+        }
+    }
+
+    pub fn cross_plane() -> Self {
+        NearestNeighborStrategy {
+            kd_tree: KdTree::new(),
+            cross_plane_only: true,
         }
     }
 }
@@ -103,7 +324,97 @@ impl Default for NearestNeighborStrategy {
 impl ConnectionStrategy for NearestNeighborStrategy {
     fn run(&mut self, model: &Model) -> ConnectionGraph {
         let mut topology: ConnectionGraph = GraphMap::new();
-        let max_connections = model.max_connections();
+        self.kd_tree = KdTree::new();
+
+        model.satellites().iter().filter(|s| s.status()).for_each(|s| {
+            topology.add_node(s.id());
+            let _ = self.kd_tree.add(s.position().as_slice().try_into().unwrap(), s.id());
+        });
+
+        // Failed satellites cannot establish links
+        let satellites = model.satellites();
+        let alive: Vec<_> = satellites.iter().filter(|s| s.status()).collect();
+        let line_of_sight = |a: usize, b: usize| can_link(model, a, b);
+
+        let nearest: Vec<Vec<(usize, bool)>> = alive.par_iter().map(|sat| {
+            let pos = sat.position().as_slice().try_into().unwrap();
+            self.kd_tree.nearest(pos, NEAREST_CANDIDATES, &squared_euclidean).unwrap().into_iter()
+                .map(|(_, &other)| (other, line_of_sight(sat.id(), other)))
+                .collect()
+        }).collect();
+
+        for (sat, nearest) in alive.iter().zip(nearest) {
+            // Satellites with free terminals after their nearest candidates keep looking further away
+            let pos = sat.position().as_slice().try_into().unwrap();
+            let further = iter::once_with(|| self.kd_tree.iter_nearest(pos, &squared_euclidean).unwrap())
+                .flatten()
+                .skip(NEAREST_CANDIDATES)
+                .map(|(_, &other)| (other, line_of_sight(sat.id(), other)));
+
+            for (other, visible) in nearest.into_iter().chain(further) {
+                if !has_free_terminal(&topology, model, sat.id()) {
+                    break;
+                }
+                if self.cross_plane_only && sat.orbital_plane().id() == satellites[other].orbital_plane().id() {
+                    continue;
+                }
+                if visible && satellites[other].status() && has_free_terminal(&topology, model, other) {
+                    topology.add_edge(sat.id(), other, model.link(sat.id(), other));
+                }
+            }
+        }
+
+        topology
+    }
+}
+
+/// Connection strategy that keeps the links of the previous topology for as
+/// long as they remain valid, preventing links from flapping between nearly
+/// equidistant neighbors. An existing link is only replaced when a candidate
+/// link is shorter by more than a configurable margin (relative to the length
+/// of the links that would have to be dropped on both ends).
+pub struct HysteresisStrategy {
+    kd_tree: KdTree<f64, usize, 3>,
+    margin: f64,
+}
+
+impl HysteresisStrategy {
+    pub fn new(margin: f64) -> Self {
+        assert!((0.0..1.0).contains(&margin));
+
+        HysteresisStrategy {
+            kd_tree: KdTree::new(),
+            margin,
+        }
+    }
+
+    /// Returns true if a satellite can accept a new link of the given length,
+    /// either because it has free terminals or because its longest link is
+    /// worse than the new one by more than the margin.
+    fn accepts_link(&self, topology: &ConnectionGraph, model: &Model, a: usize, length: f64) -> bool {
+        has_free_terminal(topology, model, a) || match longest_link(topology, a) {
+            Some((_, longest)) => length < longest * (1.0 - self.margin),
+            None => false,
+        }
+    }
+
+    /// Drops the longest link of a satellite if it has no free terminals.
+    fn free_terminal(topology: &mut ConnectionGraph, model: &Model, a: usize) {
+        if !has_free_terminal(topology, model, a) {
+            if let Some((b, _)) = longest_link(topology, a) {
+                topology.remove_edge(a, b);
+            }
+        }
+    }
+}
+
+impl ConnectionStrategy for HysteresisStrategy {
+    fn run(&mut self, model: &Model) -> ConnectionGraph {
+        initial_topology(self, model)
+    }
+
+    fn update(&mut self, model: &Model, context: &StrategyContext) -> TopologyUpdate {
+        let mut topology: ConnectionGraph = GraphMap::new();
 
         self.kd_tree = KdTree::new();
 
@@ -112,16 +423,1050 @@ impl ConnectionStrategy for NearestNeighborStrategy {
             let _ = self.kd_tree.add(s.position().as_slice().try_into().unwrap(), s.id());
         });
 
-        for sat in model.satellites() {
+        // Keep every link from the previous topology that is still valid
+        let mut previous_links: Vec<(usize, usize)> = context.previous.all_edges().map(|(a, b, _)| (a, b)).collect();
+        previous_links.sort_unstable();
+
+        for (a, b) in previous_links {
+            add_edge(&mut topology, model, a, b);
+        }
+
+        for sat in model.satellites().iter().filter(|s| s.status()) {
+            let pos = sat.position().as_slice().try_into().unwrap();
+            for other in self.kd_tree.iter_nearest(pos, &squared_euclidean).unwrap() {
+                let (a, b) = (sat.id(), *other.1);
+                let length = other.0.sqrt();
+
+                if !self.accepts_link(&topology, model, a, length) {
+                    // Remaining candidates are farther away
+                    break;
+                }
+
+                if a == b || topology.contains_edge(a, b) || !self.accepts_link(&topology, model, b, length) {
+                    continue;
+                }
+
+                let sat_b = &model.satellites()[b];
+                if !sat_b.status() || !can_link(model, a, b) {
+                    continue;
+                }
+
+                Self::free_terminal(&mut topology, model, a);
+                Self::free_terminal(&mut topology, model, b);
+                add_edge(&mut topology, model, a, b);
+            }
+        }
+
+        // Replacements may have left terminals unused on satellites that were
+        // already visited, so fill them with the nearest available neighbors
+        for sat in model.satellites().iter().filter(|s| s.status()) {
             let pos = sat.position().as_slice().try_into().unwrap();
             for other in self.kd_tree.iter_nearest(pos, &squared_euclidean).unwrap() {
-                if topology.edges(sat.id()).count() == max_connections {
+                if !has_free_terminal(&topology, model, sat.id()) {
                     break;
                 }
-                add_edge(&mut topology, model, sat.id(), *other.1);
+                if *other.1 != sat.id() {
+                    add_edge(&mut topology, model, sat.id(), *other.1);
+                }
+            }
+        }
+
+        // Most links are kept, so only the changes to the previous topology are returned
+        let added = topology.all_edges()
+            .filter(|&(a, b, _)| !context.previous.contains_edge(a, b))
+            .map(|(a, b, _)| (a, b))
+            .collect();
+        let removed = context.previous.all_edges()
+            .filter(|&(a, b, _)| !topology.contains_edge(a, b))
+            .map(|(a, b, _)| (a, b))
+            .collect();
+
+        TopologyUpdate::Diff(TopologyDiff { added, removed })
+    }
+}
+
+pub enum MatchingWeight {
+    /// Prefer shorter links.
+    InverseDistance,
+    /// Prefer links that will remain valid for longer, up to the given horizon
+    /// (in seconds).
+    Lifetime(f64),
+}
+
+/// Connection strategy that assigns links by solving a maximum-weight
+/// b-matching (where each satellite can establish at most `max_connections()`
+/// links) over the `candidates` nearest valid neighbors of each satellite.
+/// The matching is approximated with a global greedy assignment followed by
+/// a local search that swaps links whenever the total weight increases, so
+/// the result does not depend on the order in which satellites are visited.
+pub struct MatchingStrategy {
+    kd_tree: KdTree<f64, usize, 3>,
+    weight: MatchingWeight,
+    candidates: usize,
+}
+
+impl MatchingStrategy {
+    pub fn new(weight: MatchingWeight, candidates: usize) -> Self {
+        MatchingStrategy {
+            kd_tree: KdTree::new(),
+            weight,
+            candidates,
+        }
+    }
+
+    fn weighted_links(&mut self, model: &Model) -> Vec<(usize, usize, f64)> {
+        candidate_links(&mut self.kd_tree, model, self.candidates).into_iter()
+            .map(|(a, b)| {
+                let weight = match self.weight {
+                    MatchingWeight::InverseDistance => 1.0 / link_length(model, a, b),
+                    MatchingWeight::Lifetime(horizon) => link_lifetime(model, a, b, horizon),
+                };
+                (a, b, weight)
+            })
+            .collect()
+    }
+}
+
+/// Returns the lowest-weight link of a satellite in a matching.
+fn weakest_link(matching: &GraphMap<usize, f64, Undirected>, a: usize) -> Option<(usize, f64)> {
+    matching.edges(a)
+        .map(|(_, b, weight)| (b, *weight))
+        .min_by(|(_, w1), (_, w2)| w1.partial_cmp(w2).unwrap())
+}
+
+impl ConnectionStrategy for MatchingStrategy {
+    fn run(&mut self, model: &Model) -> ConnectionGraph {
+        let mut links = self.weighted_links(model);
+
+        // Heaviest links first, preferring shorter links when tied (e.g. links
+        // that outlive the horizon), and then by satellite ids for determinism
+        links.sort_by(|l1, l2| {
+            l2.2.total_cmp(&l1.2)
+                .then_with(|| link_length(model, l1.0, l1.1).total_cmp(&link_length(model, l2.0, l2.1)))
+                .then((l1.0, l1.1).cmp(&(l2.0, l2.1)))
+        });
+
+        // Edge weights of the matching are the link weights, not their lengths
+        let mut matching: GraphMap<usize, f64, Undirected> = GraphMap::new();
+        model.satellites().iter().filter(|s| s.status()).for_each(|s| {
+            matching.add_node(s.id());
+        });
+
+        let mut improved = true;
+        while improved {
+            improved = false;
+
+            for &(a, b, weight) in &links {
+                if matching.contains_edge(a, b) {
+                    continue;
+                }
+
+                // Links that would have to be dropped to free a terminal on each end
+                let satellites = model.satellites();
+                let drop_a = if matching.edges(a).count() < satellites[a].max_connections() { None } else { weakest_link(&matching, a) };
+                let drop_b = if matching.edges(b).count() < satellites[b].max_connections() { None } else { weakest_link(&matching, b) };
+
+                // Satellites without terminals have no links to drop
+                if (drop_a.is_none() && satellites[a].max_connections() == 0) || (drop_b.is_none() && satellites[b].max_connections() == 0) {
+                    continue;
+                }
+
+                let lost_weight = drop_a.map_or(0.0, |(_, w)| w) + drop_b.map_or(0.0, |(_, w)| w);
+
+                if weight > lost_weight {
+                    if let Some((x, _)) = drop_a {
+                        matching.remove_edge(a, x);
+                    }
+                    if let Some((y, _)) = drop_b {
+                        matching.remove_edge(b, y);
+                    }
+                    matching.add_edge(a, b, weight);
+
+                    // Dropped links free terminals on other satellites, which
+                    // may now accept links that were skipped earlier
+                    improved |= drop_a.is_some() || drop_b.is_some();
+                }
             }
         }
 
+        let mut topology: ConnectionGraph = GraphMap::new();
+        for node in matching.nodes() {
+            topology.add_node(node);
+        }
+        for (a, b, _) in matching.all_edges() {
+            topology.add_edge(a, b, model.link(a, b));
+        }
+
         topology
     }
 }
+
+/// Connection strategy that exploits the deterministic motion of satellites
+/// to predict how long each candidate link will remain in line of sight.
+/// Existing links are kept for as long as they are expected to survive until
+/// the next connection update, and free terminals are assigned to the
+/// candidate links with the longest predicted lifetime, reducing the
+/// frequency of reconfigurations.
+pub struct PredictiveStrategy {
+    kd_tree: KdTree<f64, usize, 3>,
+    horizon: f64,
+    candidates: usize,
+}
+
+impl PredictiveStrategy {
+    pub fn new(horizon: f64, candidates: usize) -> Self {
+        PredictiveStrategy {
+            kd_tree: KdTree::new(),
+            horizon,
+            candidates,
+        }
+    }
+}
+
+impl ConnectionStrategy for PredictiveStrategy {
+    fn run(&mut self, model: &Model) -> ConnectionGraph {
+        initial_topology(self, model)
+    }
+
+    fn update(&mut self, model: &Model, context: &StrategyContext) -> TopologyUpdate {
+        let mut topology: ConnectionGraph = GraphMap::new();
+        model.satellites().iter().filter(|s| s.status()).for_each(|s| {
+            topology.add_node(s.id());
+        });
+
+        // Connection updates are assumed to happen at regular intervals
+        let min_lifetime = context.elapsed.min(self.horizon);
+
+        let mut previous_links: Vec<(usize, usize)> = context.previous.all_edges().map(|(a, b, _)| (a, b)).collect();
+        previous_links.sort_unstable();
+
+        for (a, b) in previous_links {
+            if is_edge_valid(&topology, model, a, b) && link_lifetime(model, a, b, self.horizon) > min_lifetime {
+                topology.add_edge(a, b, model.link(a, b));
+            }
+        }
+
+        let mut links: Vec<(usize, usize, f64, f64)> = candidate_links(&mut self.kd_tree, model, self.candidates).into_iter()
+            .filter(|&(a, b)| !topology.contains_edge(a, b) && has_free_terminal(&topology, model, a) && has_free_terminal(&topology, model, b))
+            .map(|(a, b)| (a, b, link_lifetime(model, a, b, self.horizon), link_length(model, a, b)))
+            .collect();
+
+        // Longest-lived links first, preferring shorter links when tied
+        links.sort_by(|l1, l2| l2.2.partial_cmp(&l1.2).unwrap().then(l1.3.partial_cmp(&l2.3).unwrap()));
+
+        for (a, b, _, _) in links {
+            add_edge(&mut topology, model, a, b);
+        }
+
+        TopologyUpdate::Full(topology)
+    }
+}
+
+/// Offset (in orbital planes and in slots within a plane) between a satellite
+/// and the satellite it connects to.
+pub type MotifLink = (usize, i64);
+
+pub enum MotifObjective {
+    /// Minimize the average number of hops between city pairs.
+    Hops,
+    /// Minimize the average round trip time between city pairs.
+    Rtt,
+}
+
+/// Parameters for searching the motif that performs best for a set of city
+/// pairs, among all motifs whose links are within the given offsets.
+pub struct MotifSearch {
+    max_plane_offset: usize,
+    max_slot_offset: usize,
+    objective: MotifObjective,
+    city_pairs: Vec<CityPair>,
+}
+
+impl MotifSearch {
+    pub fn new(max_plane_offset: usize, max_slot_offset: usize, objective: MotifObjective, city_pairs: Vec<CityPair>) -> Self {
+        MotifSearch {
+            max_plane_offset,
+            max_slot_offset,
+            objective,
+            city_pairs,
+        }
+    }
+
+    /// Returns every link offset within the neighborhood, excluding offsets
+    /// that are equivalent to another one in the opposite direction and
+    /// offsets that would link satellites to themselves.
+    fn candidate_links(&self, model: &Model) -> Vec<MotifLink> {
+        let max_slot_offset = self.max_slot_offset as i64;
+        let mut links = Vec::new();
+
+        for plane_offset in 0..=self.max_plane_offset {
+            for slot_offset in -max_slot_offset..=max_slot_offset {
+                if (plane_offset > 0 || slot_offset > 0) && !is_self_link(model, (plane_offset, slot_offset)) {
+                    links.push((plane_offset, slot_offset));
+                }
+            }
+        }
+
+        links
+    }
+
+    /// Evaluates a topology as (unreachable city pairs, average objective
+    /// value over the reachable pairs), where lower is better.
+    fn evaluate(&self, model: &Model, topology: &ConnectionGraph) -> (usize, f64) {
+        let mut unreachable = 0;
+        let mut total = 0.0;
+
+        for pair in &self.city_pairs {
+            match routing::shortest_route(model, topology, &RoutingConfig::default(), pair.source(), pair.destination()) {
+                Some(route) => total += match self.objective {
+                    MotifObjective::Hops => route.hop_count() as f64,
+                    MotifObjective::Rtt => route.rtt(),
+                },
+                None => unreachable += 1,
+            }
+        }
+
+        let reachable = self.city_pairs.len() - unreachable;
+        (unreachable, if reachable > 0 { total / reachable as f64 } else { 0.0 })
+    }
+
+    fn best_motif(&self, model: &Model) -> Vec<MotifLink> {
+        let links = self.candidate_links(model);
+        // Each link of the motif uses two terminals of every satellite (one
+        // towards each end), so the motif is sized for the satellites with
+        // the fewest terminals
+        let terminals = model.satellites().iter().filter(|s| s.status()).map(|s| s.max_connections()).min().unwrap_or(0);
+        let motif_size = (terminals / 2).min(links.len());
+
+        let mut best: Option<((usize, f64), Vec<MotifLink>)> = None;
+
+        for indices in combinations(links.len(), motif_size) {
+            let motif: Vec<MotifLink> = indices.iter().map(|&i| links[i]).collect();
+            let score = self.evaluate(model, &tile_motif(model, &motif));
+
+            let is_better = match &best {
+                Some((best_score, _)) => score.0 < best_score.0 || (score.0 == best_score.0 && score.1 < best_score.1),
+                None => true,
+            };
+
+            if is_better {
+                best = Some((score, motif));
+            }
+        }
+
+        best.map(|(_, motif)| motif).unwrap_or_default()
+    }
+}
+
+/// Returns all k-element subsets of {0, ..., n - 1}, in lexicographic order,
+/// generating each subset only when it is needed.
+fn combinations(n: usize, k: usize) -> impl Iterator<Item = Vec<usize>> {
+    let first = (k <= n).then(|| (0..k).collect::<Vec<usize>>());
+
+    iter::successors(first, move |current| {
+        // Increment the last index that can still be incremented, and reset the following ones
+        let i = (0..k).rev().find(|&i| current[i] < n - k + i)?;
+
+        let mut next = current.clone();
+        next[i] += 1;
+        for j in i + 1..k {
+            next[j] = next[j - 1] + 1;
+        }
+
+        Some(next)
+    })
+}
+
+/// Whether a motif link connects every satellite to itself, since its
+/// offsets are whole turns of the constellation.
+fn is_self_link(model: &Model, (plane_offset, slot_offset): MotifLink) -> bool {
+    let num_planes = model.orbital_planes().len();
+    let sats_per_plane = model.satellites().len() / num_planes;
+
+    plane_offset % num_planes == 0 && slot_offset.rem_euclid(sats_per_plane as i64) == 0
+}
+
+/// Connects every satellite to the satellites at each of the offsets of
+/// the motif.
+fn tile_motif(model: &Model, motif: &[MotifLink]) -> ConnectionGraph {
+    let mut topology = GraphMap::new();
+    model.satellites().iter().filter(|s| s.status()).for_each(|s| {
+        topology.add_node(s.id());
+    });
+
+    let num_sats = model.satellites().len();
+    let num_planes = model.orbital_planes().len();
+    let sats_per_plane = num_sats / num_planes;
+
+    for &(plane_offset, slot_offset) in motif {
+        for plane in 0..num_planes {
+            for sat in 0..sats_per_plane {
+                let other_plane = (plane + plane_offset) % num_planes;
+                let other_sat = (sat as i64 + slot_offset).rem_euclid(sats_per_plane as i64) as usize;
+
+                add_edge(
+                    &mut topology,
+                    model,
+                    plane * sats_per_plane + sat,
+                    other_plane * sats_per_plane + other_sat,
+                );
+            }
+        }
+    }
+
+    topology
+}
+
+/// Connection strategy that tiles a small connectivity pattern (motif) across
+/// the constellation: every satellite connects to the satellites at the given
+/// plane and slot offsets. The +Grid pattern corresponds to the motif
+/// `[(0, 1), (1, 0)]`. When a search is configured, the motif is chosen again
+/// at every connection update by evaluating all motifs in a neighborhood.
+pub struct MotifStrategy {
+    motif: Vec<MotifLink>,
+    search: Option<MotifSearch>,
+}
+
+impl MotifStrategy {
+    pub fn new(motif: Vec<MotifLink>, search: Option<MotifSearch>) -> Self {
+        MotifStrategy {
+            motif,
+            search,
+        }
+    }
+
+    pub fn motif(&self) -> &[MotifLink] {
+        &self.motif
+    }
+}
+
+impl ConnectionStrategy for MotifStrategy {
+    fn run(&mut self, model: &Model) -> ConnectionGraph {
+        if let Some(search) = &self.search {
+            self.motif = search.best_motif(model);
+        }
+
+        tile_motif(model, &self.motif)
+    }
+}
+
+/// Number of nearby satellites that are considered when a strategy modifies
+/// the topology locally (e.g. around an articulation point).
+const NEIGHBORHOOD_SIZE: usize = 16;
+
+/// Labels each satellite in the topology with the connected component it
+/// belongs to, ignoring the given satellites and link. Satellites that are not
+/// part of the topology (or are ignored) are labeled with `usize::MAX`.
+fn component_labels(topology: &ConnectionGraph, num_satellites: usize,
+        excluded_nodes: &[usize], excluded_edge: Option<(usize, usize)>) -> Vec<usize> {
+    let mut labels = vec![usize::MAX; num_satellites];
+    let mut next_label = 0;
+    let mut stack = Vec::new();
+
+    let is_excluded_edge = |a: usize, b: usize| excluded_edge == Some((a, b)) || excluded_edge == Some((b, a));
+
+    for root in topology.nodes() {
+        if labels[root] != usize::MAX || excluded_nodes.contains(&root) {
+            continue;
+        }
+
+        labels[root] = next_label;
+        stack.push(root);
+
+        while let Some(node) = stack.pop() {
+            for neighbor in topology.neighbors(node) {
+                if labels[neighbor] == usize::MAX && !excluded_nodes.contains(&neighbor) && !is_excluded_edge(node, neighbor) {
+                    labels[neighbor] = next_label;
+                    stack.push(neighbor);
+                }
+            }
+        }
+
+        next_label += 1;
+    }
+
+    labels
+}
+
+/// Connection strategy that augments the topology of a base strategy in order
+/// to make it resilient to failures, using the terminals that the base
+/// strategy leaves unused (e.g. on the neighbors of failed satellites). Links
+/// are added to reconnect partitioned components and, for `k >= 2`, to
+/// eliminate articulation points and bridges. For `k >= 3`, satellites are
+/// also linked to their nearest available neighbors until they have at least
+/// `k` links, and links are then added across every remaining set of fewer
+/// than `k` satellites whose failure would partition the topology.
+pub struct KConnectedStrategy {
+    base: Box<dyn ConnectionStrategy>,
+    k: usize,
+    kd_tree: KdTree<f64, usize, 3>,
+}
+
+impl KConnectedStrategy {
+    pub fn new(base: Box<dyn ConnectionStrategy>, k: usize) -> Self {
+        KConnectedStrategy {
+            base,
+            k,
+            kd_tree: KdTree::new(),
+        }
+    }
+
+    fn nearby_satellites(&self, model: &Model, sat: usize, n: usize) -> Vec<usize> {
+        let pos = model.satellites()[sat].position().as_slice().try_into().unwrap();
+        self.kd_tree.nearest(pos, n, &squared_euclidean).unwrap().into_iter().map(|(_, &id)| id).collect()
+    }
+
+    /// Finds the shortest valid link between two of the given satellites that
+    /// belong to different components.
+    fn shortest_bridging_link(topology: &ConnectionGraph, model: &Model,
+            satellites: &[usize], labels: &[usize]) -> Option<(usize, usize)> {
+        let mut best: Option<(f64, (usize, usize))> = None;
+
+        for (i, &a) in satellites.iter().enumerate() {
+            for &b in &satellites[i + 1..] {
+                if labels[a] == usize::MAX || labels[b] == usize::MAX || labels[a] == labels[b] {
+                    continue;
+                }
+                if topology.contains_edge(a, b) || !is_edge_valid(topology, model, a, b) {
+                    continue;
+                }
+
+                let length = link_length(model, a, b);
+                if best.is_none_or(|(best_length, _)| length < best_length) {
+                    best = Some((length, (a, b)));
+                }
+            }
+        }
+
+        best.map(|(_, link)| link)
+    }
+
+    /// Adds links between satellites with unused terminals that belong to
+    /// different components, until no more such links can be established.
+    fn connect_components(&self, topology: &mut ConnectionGraph, model: &Model) {
+        loop {
+            let labels = component_labels(topology, model.satellites().len(), &[], None);
+
+            let mut best: Option<(f64, (usize, usize))> = None;
+            for a in topology.nodes().filter(|&a| has_free_terminal(topology, model, a)) {
+                let nearby = self.nearby_satellites(model, a, NEIGHBORHOOD_SIZE);
+                if let Some((x, y)) = Self::shortest_bridging_link(topology, model, &[vec![a], nearby].concat(), &labels) {
+                    let length = link_length(model, x, y);
+                    if best.is_none_or(|(best_length, _)| length < best_length) {
+                        best = Some((length, (x, y)));
+                    }
+                }
+            }
+
+            match best {
+                Some((_, (a, b))) => add_edge(topology, model, a, b),
+                None => break,
+            }
+        }
+    }
+
+    /// Adds links around articulation points and bridges so that their
+    /// failure would no longer partition the topology.
+    fn eliminate_critical_elements(&self, topology: &mut ConnectionGraph, model: &Model) {
+        let num_satellites = model.satellites().len();
+        let mut unfixable_points = Vec::new();
+        let mut unfixable_bridges = Vec::new();
+
+        loop {
+            let critical = critical_elements(num_satellites, topology);
+
+            let point = critical.articulation_points.into_iter().find(|p| !unfixable_points.contains(p));
+            if let Some(point) = point {
+                let labels = component_labels(topology, num_satellites, &[point], None);
+                let nearby = self.nearby_satellites(model, point, NEIGHBORHOOD_SIZE);
+
+                match Self::shortest_bridging_link(topology, model, &nearby, &labels) {
+                    Some((a, b)) => add_edge(topology, model, a, b),
+                    None => unfixable_points.push(point),
+                }
+                continue;
+            }
+
+            let bridge = critical.bridges.into_iter().find(|b| !unfixable_bridges.contains(b));
+            if let Some((u, v)) = bridge {
+                let labels = component_labels(topology, num_satellites, &[], Some((u, v)));
+                let nearby = [self.nearby_satellites(model, u, NEIGHBORHOOD_SIZE), self.nearby_satellites(model, v, NEIGHBORHOOD_SIZE)].concat();
+
+                match Self::shortest_bridging_link(topology, model, &nearby, &labels) {
+                    Some((a, b)) => add_edge(topology, model, a, b),
+                    None => unfixable_bridges.push((u, v)),
+                }
+                continue;
+            }
+
+            break;
+        }
+    }
+
+    /// Links satellites with fewer than `k` links to their nearest neighbors.
+    fn ensure_min_degree(&self, topology: &mut ConnectionGraph, model: &Model) {
+        let nodes: Vec<usize> = topology.nodes().collect();
+
+        for a in nodes {
+            for b in self.nearby_satellites(model, a, NEIGHBORHOOD_SIZE) {
+                if topology.edges(a).count() >= self.k {
+                    break;
+                }
+                if a != b && !topology.contains_edge(a, b) {
+                    add_edge(topology, model, a, b);
+                }
+            }
+        }
+    }
+
+    /// Adds links across sets of fewer than `k` satellites whose failure
+    /// would partition the topology. Every minimal such set contains a
+    /// satellite with neighbors on both sides, so the sets are found by
+    /// counting the vertex-disjoint paths between the neighbors of every
+    /// satellite. Satellites are checked again whenever they gain a link.
+    fn eliminate_vertex_cuts(&self, topology: &mut ConnectionGraph, model: &Model) {
+        let num_satellites = model.satellites().len();
+        let mut paths = VertexDisjointPaths::new(topology, num_satellites);
+        let mut pending: Vec<usize> = topology.nodes().collect();
+        pending.reverse();
+
+        while let Some(sat) = pending.pop() {
+            let neighbors: Vec<usize> = topology.neighbors(sat).collect();
+
+            for (i, &a) in neighbors.iter().enumerate() {
+                for &b in &neighbors[i + 1..] {
+                    while !topology.contains_edge(a, b) {
+                        let cut = match paths.small_cut(a, b, self.k) {
+                            Some(cut) => cut,
+                            None => break,
+                        };
+
+                        let labels = component_labels(topology, num_satellites, &cut, None);
+                        let nearby = self.nearby_satellites(model, sat, NEIGHBORHOOD_SIZE);
+                        match Self::shortest_bridging_link(topology, model, &nearby, &labels) {
+                            Some((x, y)) => {
+                                add_edge(topology, model, x, y);
+                                paths = VertexDisjointPaths::new(topology, num_satellites);
+                                pending.extend([x, y]);
+                            },
+                            None => break,
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ConnectionStrategy for KConnectedStrategy {
+    fn run(&mut self, model: &Model) -> ConnectionGraph {
+        initial_topology(self, model)
+    }
+
+    fn update(&mut self, model: &Model, context: &StrategyContext) -> TopologyUpdate {
+        let mut topology = match self.base.update(model, context) {
+            TopologyUpdate::Full(topology) => topology,
+            TopologyUpdate::Diff(diff) => diff.apply(context.previous, model),
+        };
+
+        self.kd_tree = KdTree::new();
+        for sat in model.satellites().iter().filter(|s| s.status()) {
+            let _ = self.kd_tree.add(sat.position().as_slice().try_into().unwrap(), sat.id());
+        }
+
+        if self.k >= 1 {
+            self.connect_components(&mut topology, model);
+        }
+        if self.k >= 2 {
+            self.eliminate_critical_elements(&mut topology, model);
+        }
+        if self.k >= 3 {
+            self.ensure_min_degree(&mut topology, model);
+            self.eliminate_vertex_cuts(&mut topology, model);
+        }
+
+        TopologyUpdate::Full(topology)
+    }
+}
+
+pub enum TrafficObjective {
+    /// Minimize the average round trip time, weighted by demand volume.
+    Latency,
+    /// Maximize the volume of traffic that is delivered when every demand is
+    /// routed through its shortest path, limited by the capacities of the
+    /// links. Demands that share an overloaded link are scaled down
+    /// proportionally.
+    Throughput,
+}
+
+/// Round trip time (in seconds) assigned to demands that cannot be routed.
+const UNREACHABLE_RTT: f64 = 10.0;
+
+/// Connection strategy that optimizes the topology for a traffic matrix. The
+/// topology of a base strategy (usually the grid) is improved through a
+/// local search that swaps the endpoints of pairs of nearby links, keeping
+/// swaps that improve the objective, until a time budget or a maximum
+/// number of iterations is exhausted.
+pub struct TrafficAwareStrategy {
+    base: Box<dyn ConnectionStrategy>,
+    demands: Vec<TrafficDemand>,
+    objective: TrafficObjective,
+    time_budget: Duration,
+    max_iterations: usize,
+    kd_tree: KdTree<f64, usize, 3>,
+    rng: StdRng,
+}
+
+impl TrafficAwareStrategy {
+    pub fn new(
+        base: Box<dyn ConnectionStrategy>,
+        demands: Vec<TrafficDemand>,
+        objective: TrafficObjective,
+        time_budget: f64,
+        max_iterations: usize,
+        seed: u64,
+    ) -> Self {
+        TrafficAwareStrategy {
+            base,
+            demands,
+            objective,
+            time_budget: Duration::from_secs_f64(time_budget),
+            max_iterations,
+            kd_tree: KdTree::new(),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Returns the cost of a topology (lower is better) and the links used
+    /// by the routes of the demands.
+    fn evaluate(&self, model: &Model, topology: &ConnectionGraph) -> (f64, Vec<(usize, usize)>) {
+        match self.objective {
+            TrafficObjective::Latency => {
+                let routes: Vec<Option<Route>> = self.demands.iter()
+                    .map(|d| routing::shortest_route(model, topology, &RoutingConfig::default(), d.source(), d.destination()))
+                    .collect();
+                let used_links = routes.iter().flatten().flat_map(Route::satellite_links).collect();
+
+                let total_volume: f64 = self.demands.iter().map(TrafficDemand::volume).sum();
+                let weighted_rtt: f64 = self.demands.iter().zip(&routes).map(|(d, route)| {
+                    d.volume() * route.as_ref().map_or(UNREACHABLE_RTT, Route::rtt)
+                }).sum();
+
+                (if total_volume > 0.0 { weighted_rtt / total_volume } else { 0.0 }, used_links)
+            },
+            TrafficObjective::Throughput => {
+                let assignment = FlowAssignment::new(model, topology, &RoutingConfig::default(), &self.demands);
+                let used_links = assignment.link_loads().map(|(link, _)| link).collect();
+
+                (-assignment.delivered().iter().sum::<f64>(), used_links)
+            },
+        }
+    }
+
+    /// Attempts to replace links (a, b) and (c, d) with (a, c) and (b, d),
+    /// which keeps the number of links of every satellite unchanged.
+    fn swap_links(topology: &ConnectionGraph, model: &Model, (a, b): (usize, usize), (c, d): (usize, usize)) -> Option<ConnectionGraph> {
+        let distinct = a != c && a != d && b != c && b != d;
+        if !distinct || topology.contains_edge(a, c) || topology.contains_edge(b, d) {
+            return None;
+        }
+
+        if !can_link(model, a, c) || !can_link(model, b, d) {
+            return None;
+        }
+
+        let mut swapped = topology.clone();
+        swapped.remove_edge(a, b);
+        swapped.remove_edge(c, d);
+        swapped.add_edge(a, c, model.link(a, c));
+        swapped.add_edge(b, d, model.link(b, d));
+
+        Some(swapped)
+    }
+}
+
+impl ConnectionStrategy for TrafficAwareStrategy {
+    fn run(&mut self, model: &Model) -> ConnectionGraph {
+        initial_topology(self, model)
+    }
+
+    fn update(&mut self, model: &Model, context: &StrategyContext) -> TopologyUpdate {
+        let start = Instant::now();
+
+        let mut topology = match self.base.update(model, context) {
+            TopologyUpdate::Full(topology) => topology,
+            TopologyUpdate::Diff(diff) => diff.apply(context.previous, model),
+        };
+
+        if self.demands.is_empty() {
+            return TopologyUpdate::Full(topology);
+        }
+
+        self.kd_tree = KdTree::new();
+        for sat in model.satellites().iter().filter(|s| s.status()) {
+            let _ = self.kd_tree.add(sat.position().as_slice().try_into().unwrap(), sat.id());
+        }
+
+        let (mut cost, mut used_links) = self.evaluate(model, &topology);
+
+        for _ in 0..self.max_iterations {
+            if start.elapsed() >= self.time_budget || used_links.is_empty() {
+                break;
+            }
+
+            // Swap a link used by the current routes with a link nearby
+            let (a, b) = *used_links.choose(&mut self.rng).unwrap();
+            let (a, b) = if self.rng.gen() { (a, b) } else { (b, a) };
+
+            let pos = model.satellites()[a].position().as_slice().try_into().unwrap();
+            let nearby = self.kd_tree.nearest(pos, NEIGHBORHOOD_SIZE, &squared_euclidean).unwrap();
+            let c = *nearby.choose(&mut self.rng).unwrap().1;
+            let neighbors: Vec<usize> = topology.neighbors(c).collect();
+            let Some(&d) = neighbors.choose(&mut self.rng) else { continue };
+
+            if let Some(swapped) = Self::swap_links(&topology, model, (a, b), (c, d)) {
+                let (swapped_cost, swapped_links) = self.evaluate(model, &swapped);
+                if swapped_cost < cost {
+                    topology = swapped;
+                    cost = swapped_cost;
+                    used_links = swapped_links;
+                }
+            }
+        }
+
+        TopologyUpdate::Full(topology)
+    }
+}
+
+/// Strategy that is part of a hybrid strategy, which can only use the given
+/// number of terminals of each satellite.
+pub struct HybridComponent {
+    strategy: Box<dyn ConnectionStrategy>,
+    terminals: usize,
+    previous: ConnectionGraph,
+}
+
+impl HybridComponent {
+    pub fn new(strategy: Box<dyn ConnectionStrategy>, terminals: usize) -> Self {
+        HybridComponent {
+            strategy,
+            terminals,
+            previous: GraphMap::new(),
+        }
+    }
+}
+
+/// Connection strategy that splits the terminals of each satellite between
+/// several strategies (e.g. fixed intra-plane links established by a grid
+/// and steerable cross-plane links established by a nearest neighbor
+/// strategy) and merges their links into a single topology. Terminals are
+/// assigned to components in order, so satellites with fewer terminals than
+/// the components require only take part in the first components.
+pub struct HybridStrategy {
+    components: Vec<HybridComponent>,
+}
+
+impl HybridStrategy {
+    pub fn new(components: Vec<HybridComponent>) -> Self {
+        HybridStrategy {
+            components,
+        }
+    }
+}
+
+impl ConnectionStrategy for HybridStrategy {
+    fn run(&mut self, model: &Model) -> ConnectionGraph {
+        initial_topology(self, model)
+    }
+
+    fn update(&mut self, model: &Model, context: &StrategyContext) -> TopologyUpdate {
+        let mut topology: ConnectionGraph = GraphMap::new();
+        model.satellites().iter().filter(|s| s.status()).for_each(|s| {
+            topology.add_node(s.id());
+        });
+
+        // Terminals already owned by previous components
+        let mut owned = 0;
+
+        for component in &mut self.components {
+            // Pairs linked by previous components cannot be linked again
+            let component_model = model
+                .with_terminals(|sat| component.terminals.min(sat.max_connections().saturating_sub(owned)))
+                .without_links(topology.all_edges().map(|(a, b, _)| (a, b)));
+            owned += component.terminals;
+
+            // Each component only sees the links it established itself
+            for &id in context.failures {
+                component.previous.remove_node(id);
+            }
+            let component_context = StrategyContext {
+                previous: &component.previous,
+                ..*context
+            };
+
+            component.previous = match component.strategy.update(&component_model, &component_context) {
+                TopologyUpdate::Full(topology) => topology,
+                TopologyUpdate::Diff(diff) => diff.apply(&component.previous, &component_model),
+            };
+
+            for (a, b, link) in component.previous.all_edges() {
+                topology.add_edge(a, b, *link);
+            }
+        }
+
+        TopologyUpdate::Full(topology)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{ConstellationType, GeoCoordinates, EARTH_RADIUS};
+
+    fn model() -> Model {
+        Model::new(6, 11, 86.4f64.to_radians(), ConstellationType::Star, 0, EARTH_RADIUS + 0.781e6, 4)
+    }
+
+    /// Strategy with state that cannot be shared between threads.
+    struct CountingStrategy {
+        runs: std::cell::Cell<usize>,
+    }
+
+    impl ConnectionStrategy for CountingStrategy {
+        fn run(&mut self, model: &Model) -> ConnectionGraph {
+            self.runs.set(self.runs.get() + 1);
+            GridStrategy::new(1, None, true).run(model)
+        }
+    }
+
+    #[test]
+    fn strategies_need_not_be_sync() {
+        let mut strategy: Box<dyn ConnectionStrategy> = Box::new(CountingStrategy { runs: Default::default() });
+        let model = model();
+
+        let topology = strategy.run(&model);
+        let context = StrategyContext { previous: &topology, t: 0.0, elapsed: 0.0, failures: &[] };
+        assert!(matches!(strategy.update(&model, &context), TopologyUpdate::Full(t) if t.edge_count() == topology.edge_count()));
+    }
+
+    /// Updates a hysteresis strategy from a topology where satellite 0 is
+    /// linked to a farther neighbor than its nearest one, returning whether
+    /// that link is kept.
+    fn hysteresis_keeps_longer_link(margin: f64, model: &Model, farther: usize) -> bool {
+        let mut previous: ConnectionGraph = GraphMap::new();
+        previous.add_edge(0, farther, model.link(0, farther));
+        let context = StrategyContext { previous: &previous, t: model.t(), elapsed: 0.0, failures: &[] };
+
+        match HysteresisStrategy::new(margin).update(model, &context) {
+            TopologyUpdate::Diff(diff) => !diff.removed.iter().any(|&(a, b)| a.min(b) == 0 && a.max(b) == farther),
+            TopologyUpdate::Full(topology) => topology.contains_edge(0, farther),
+        }
+    }
+
+    #[test]
+    fn hysteresis_replaces_links_beyond_margin() {
+        let model = model();
+
+        let mut neighbors: Vec<(usize, f64)> = (1..model.satellites().len())
+            .filter(|&b| can_link(&model, 0, b))
+            .map(|b| (b, link_length(&model, 0, b)))
+            .collect();
+        neighbors.sort_by(|(_, l1), (_, l2)| l1.total_cmp(l2));
+        let nearest = neighbors[0].1;
+        let &(farther, length) = neighbors.iter().find(|(_, l)| *l > 1.5 * nearest).unwrap();
+
+        // Satellite 0 has a single terminal, while the other end of its link
+        // has enough terminals to never drop it
+        let num_satellites = model.satellites().len();
+        let model = model.with_terminals(|s| if s.id() == farther { num_satellites } else { 1 });
+
+        // The nearest neighbor is shorter by a fraction `gain` of the current link
+        let gain = 1.0 - nearest / length;
+        assert!(hysteresis_keeps_longer_link(gain + 0.01, &model, farther));
+        assert!(!hysteresis_keeps_longer_link(gain - 0.01, &model, farther));
+    }
+
+    #[test]
+    fn matching_respects_terminals_and_beats_greedy() {
+        let model = model().with_terminals(|s| s.id() % 3 + 1);
+        let candidates = 8;
+        let topology = MatchingStrategy::new(MatchingWeight::InverseDistance, candidates).run(&model);
+
+        for sat in model.satellites() {
+            assert!(topology.edges(sat.id()).count() <= sat.max_connections());
+        }
+
+        // Greedy assignment of the same candidates, heaviest (shortest) first
+        let mut links = candidate_links(&mut KdTree::new(), &model, candidates);
+        links.sort_by(|&(a, b), &(c, d)| link_length(&model, a, b).total_cmp(&link_length(&model, c, d)));
+        let mut greedy: ConnectionGraph = GraphMap::new();
+        for (a, b) in links {
+            if has_free_terminal(&greedy, &model, a) && has_free_terminal(&greedy, &model, b) {
+                greedy.add_edge(a, b, model.link(a, b));
+            }
+        }
+
+        let total_weight = |g: &ConnectionGraph| g.all_edges().map(|(_, _, link)| 1.0 / link.length).sum::<f64>();
+        assert!(total_weight(&topology) >= total_weight(&greedy) * (1.0 - 1e-12));
+    }
+
+    #[test]
+    fn traffic_aware_throughput_is_at_least_base() {
+        let model = model();
+        // Nearby demands that share the links of their shortest routes in the grid
+        let demands: Vec<TrafficDemand> = [((51.5, -0.1), (40.7, -74.0)), ((48.9, 2.4), (42.4, -71.1)), ((53.3, -6.3), (45.5, -73.6))]
+            .into_iter()
+            .map(|((lat1, lon1), (lat2, lon2))| TrafficDemand::new(GeoCoordinates::new(lat1, lon1), GeoCoordinates::new(lat2, lon2), 8.0))
+            .collect();
+        let delivered = |topology: &ConnectionGraph| -> f64 {
+            FlowAssignment::new(&model, topology, &RoutingConfig::default(), &demands).delivered().iter().sum()
+        };
+
+        let base = GridStrategy::new(0, None, true).run(&model);
+        // The search is only limited by the number of iterations, so that the result is deterministic
+        let mut strategy = TrafficAwareStrategy::new(Box::new(GridStrategy::new(0, None, true)), demands.clone(), TrafficObjective::Throughput, 3600.0, 200, 0);
+        let optimized = strategy.run(&model);
+
+        // Every route of the grid crosses the same congested link, which the search avoids
+        assert!(delivered(&optimized) > delivered(&base));
+        for sat in model.satellites() {
+            assert!(optimized.edges(sat.id()).count() <= sat.max_connections());
+        }
+    }
+
+    #[test]
+    fn nearest_neighbor_respects_terminal_counts() {
+        let model = model().with_terminals(|s| s.id() % 4 + 1);
+
+        for mut strategy in [NearestNeighborStrategy::new(), NearestNeighborStrategy::cross_plane()] {
+            let topology = strategy.run(&model);
+
+            assert!(topology.all_edges().all(|(a, b, _)| a != b));
+            for sat in model.satellites() {
+                assert!(topology.edges(sat.id()).count() <= sat.max_connections());
+            }
+            // Satellites with a single terminal still get a link
+            assert!(model.satellites().iter().any(|s| s.max_connections() == 1 && topology.edges(s.id()).count() == 1));
+        }
+    }
+
+    #[test]
+    fn motif_search_fits_every_satellite() {
+        let model = model().with_terminals(|s| if s.id() % 2 == 0 { 2 } else { 4 });
+        let city_pairs = vec![CityPair::new("pair", GeoCoordinates::new(40.7, -74.0), GeoCoordinates::new(51.5, -0.1))];
+        let num_planes = model.orbital_planes().len();
+
+        // Offsets of a whole turn of planes are never candidates
+        let search = MotifSearch::new(num_planes, 1, MotifObjective::Hops, city_pairs);
+        assert!(!search.candidate_links(&model).contains(&(num_planes, 0)));
+
+        let mut strategy = MotifStrategy::new(Vec::new(), Some(search));
+        let topology = strategy.run(&model);
+
+        assert_eq!(strategy.motif().len(), 1);
+        assert!(topology.all_edges().all(|(a, b, _)| a != b));
+        for sat in model.satellites() {
+            assert!(topology.edges(sat.id()).count() <= sat.max_connections());
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::BinaryHeap;
+
+use crate::{
+    connection_strategy::{ConnectionStrategy, StrategyContext, TopologyUpdate},
+    model::{Model, ConnectionGraph, GeoCoordinates, LIGHT_SPEED},
+    routing::{RouteNode, RoutingConfig, RoutingGraph, SearchState},
+};
+
+/// Settings used to predict the contact plan during the simulation.
+pub struct ContactPlanConfig {
+    /// Time span (in seconds) covered by the plan.
+    pub horizon: f64,
+    /// Time (in seconds) between the predicted topologies.
+    pub interval: f64,
+    /// Builds the strategy used to predict future topologies, which should be
+    /// configured like the strategy of the simulation. A new instance is used
+    /// for every prediction, so that the state of stateful strategies does not
+    /// carry over from one prediction to the next.
+    pub new_strategy: Box<dyn Fn() -> Box<dyn ConnectionStrategy> + Send>,
+}
+
+/// Period during which a node can send data to another node.
+#[derive(Clone, Copy)]
+pub struct Contact {
+    pub from: usize,
+    pub to: usize,
+    pub start: f64,
+    pub end: f64,
+    /// One-way propagation delay, in seconds.
+    pub delay: f64,
+}
+
+/// Contact traversed by a contact graph route.
+pub struct ContactHop {
+    pub from: RouteNode,
+    pub to: RouteNode,
+    /// Time at which the data leaves `from`, possibly after being stored
+    /// while waiting for the contact to start.
+    pub departure: f64,
+    pub arrival: f64,
+}
+
+/// Path found through a contact plan, on which nodes may store data until a
+/// future contact becomes available.
+pub struct ContactRoute {
+    departure: f64,
+    hops: Vec<ContactHop>,
+}
+
+impl ContactRoute {
+    pub fn hops(&self) -> &[ContactHop] {
+        &self.hops
+    }
+
+    pub fn departure(&self) -> f64 {
+        self.departure
+    }
+
+    pub fn arrival(&self) -> f64 {
+        self.hops.last().map_or(self.departure, |h| h.arrival)
+    }
+
+    /// Time (in seconds) between the departure from the source and the
+    /// arrival at the destination.
+    pub fn delay(&self) -> f64 {
+        self.arrival() - self.departure
+    }
+}
+
+/// Contacts between satellites, gateways and ground sites over a time
+/// horizon, derived from successive snapshots of the topology. Nodes are
+/// identified as in the `RoutingGraph`.
+pub struct ContactPlan {
+    contacts: Vec<Contact>,
+    /// Indices of the contacts starting at each node.
+    outgoing: Vec<Vec<usize>>,
+    num_satellites: usize,
+    first_site: usize,
+}
+
+impl ContactPlan {
+    /// Predicts the contacts between a set of ground sites over a time
+    /// horizon, by propagating a copy of the model and running a strategy on
+    /// it every `interval` seconds, starting from the current topology.
+    pub fn predict(
+        model: &Model,
+        topology: &ConnectionGraph,
+        config: &RoutingConfig,
+        sites: &[GeoCoordinates],
+        strategy: &mut dyn ConnectionStrategy,
+        horizon: f64,
+        interval: f64,
+    ) -> Self {
+        assert!(horizon > 0.0 && interval > 0.0);
+
+        let num_satellites = model.satellites().len();
+        let first_site = num_satellites + config.gateways().len();
+        let num_nodes = first_site + sites.len();
+
+        let mut plan = ContactPlan {
+            contacts: Vec::new(),
+            outgoing: vec![Vec::new(); num_nodes],
+            num_satellites,
+            first_site,
+        };
+
+        let mut model = model.clone();
+        let mut topology = topology.clone();
+        let start = model.t();
+        let num_snapshots = (horizon / interval).ceil() as usize;
+
+        for i in 0..num_snapshots {
+            if i > 0 {
+                model.increment_t(interval);
+
+                let context = StrategyContext {
+                    previous: &topology,
+                    t: model.t(),
+                    elapsed: interval,
+                    failures: &[],
+                };
+                topology = match strategy.update(&model, &context) {
+                    TopologyUpdate::Full(topology) => topology,
+                    TopologyUpdate::Diff(diff) => diff.apply(&topology, &model),
+                };
+            }
+
+            let snapshot_start = start + i as f64 * interval;
+            let snapshot_end = (start + (i + 1) as f64 * interval).min(start + horizon);
+            plan.add_snapshot(&RoutingGraph::new(&model, &topology, config, sites), snapshot_start, snapshot_end);
+        }
+
+        plan
+    }
+
+    /// Adds the links of a routing graph as contacts between `start` and `end`.
+    fn add_snapshot(&mut self, graph: &RoutingGraph, start: f64, end: f64) {
+        for (from, to, length) in graph.links() {
+            self.outgoing[from].push(self.contacts.len());
+            self.contacts.push(Contact { from, to, start, end, delay: length / LIGHT_SPEED });
+        }
+    }
+
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    /// Finds the route with the earliest arrival between two ground sites
+    /// (given by their indices in the list of sites of the plan), for data
+    /// leaving the source at time `t`.
+    pub fn earliest_arrival(&self, from: usize, to: usize, t: f64) -> Option<ContactRoute> {
+        let source = self.first_site + from;
+        let target = self.first_site + to;
+        assert!(source < self.outgoing.len() && target < self.outgoing.len());
+
+        let mut arrivals = vec![f64::INFINITY; self.outgoing.len()];
+        let mut predecessors: Vec<Option<(usize, f64)>> = vec![None; self.outgoing.len()];
+        let mut queue = BinaryHeap::new();
+
+        arrivals[source] = t;
+        queue.push(SearchState { estimate: t, node: source });
+
+        while let Some(SearchState { estimate, node }) = queue.pop() {
+            if estimate > arrivals[node] {
+                continue;
+            }
+            if node == target {
+                break;
+            }
+            // Ground sites other than the source never relay data
+            if node >= self.first_site && node != source {
+                continue;
+            }
+
+            for &i in &self.outgoing[node] {
+                let contact = &self.contacts[i];
+                let departure = estimate.max(contact.start);
+                let arrival = departure + contact.delay;
+
+                if departure < contact.end && arrival < arrivals[contact.to] {
+                    arrivals[contact.to] = arrival;
+                    predecessors[contact.to] = Some((node, departure));
+                    queue.push(SearchState { estimate: arrival, node: contact.to });
+                }
+            }
+        }
+
+        if arrivals[target].is_infinite() {
+            return None;
+        }
+
+        let route_node = |id: usize| match id {
+            _ if id < self.num_satellites => RouteNode::Satellite(id),
+            _ if id == source => RouteNode::Source,
+            _ if id == target => RouteNode::Destination,
+            _ => RouteNode::Gateway(id - self.num_satellites),
+        };
+
+        let mut hops = Vec::new();
+        let mut node = target;
+        while let Some((previous, departure)) = predecessors[node] {
+            hops.push(ContactHop { from: route_node(previous), to: route_node(node), departure, arrival: arrivals[node] });
+            node = previous;
+        }
+        hops.reverse();
+
+        Some(ContactRoute { departure: t, hops })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plan with three satellites and two ground sites (nodes 3 and 4).
+    fn contact_plan() -> ContactPlan {
+        let contacts = vec![
+            Contact { from: 3, to: 0, start: 0.0, end: 10.0, delay: 0.1 },
+            Contact { from: 0, to: 4, start: 0.0, end: 2.0, delay: 0.1 },
+            Contact { from: 0, to: 1, start: 5.0, end: 6.0, delay: 0.1 },
+            Contact { from: 1, to: 4, start: 0.0, end: 100.0, delay: 0.1 },
+            Contact { from: 3, to: 2, start: 0.0, end: 100.0, delay: 1.0 },
+            Contact { from: 2, to: 4, start: 20.0, end: 30.0, delay: 0.1 },
+        ];
+
+        let mut outgoing = vec![Vec::new(); 5];
+        for (i, contact) in contacts.iter().enumerate() {
+            outgoing[contact.from].push(i);
+        }
+
+        ContactPlan { contacts, outgoing, num_satellites: 3, first_site: 3 }
+    }
+
+    fn hops(route: &ContactRoute) -> Vec<(RouteNode, RouteNode, f64, f64)> {
+        route.hops().iter().map(|h| (h.from, h.to, h.departure, h.arrival)).collect()
+    }
+
+    #[test]
+    fn earliest_arrival_waits_for_contacts() {
+        let plan = contact_plan();
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        // The direct contact is still available
+        let route = plan.earliest_arrival(0, 1, 0.0).unwrap();
+        assert!(close(route.arrival(), 0.2));
+        assert_eq!(route.hops().len(), 2);
+
+        // Once it ends, data is stored in satellite 0 until it can reach satellite 1
+        let route = plan.earliest_arrival(0, 1, 3.0).unwrap();
+        let expected = [
+            (RouteNode::Source, RouteNode::Satellite(0), 3.0, 3.1),
+            (RouteNode::Satellite(0), RouteNode::Satellite(1), 5.0, 5.1),
+            (RouteNode::Satellite(1), RouteNode::Destination, 5.1, 5.2),
+        ];
+        assert_eq!(route.hops().len(), expected.len());
+        for (hop, expected) in hops(&route).into_iter().zip(expected) {
+            assert!(hop.0 == expected.0 && hop.1 == expected.1 && close(hop.2, expected.2) && close(hop.3, expected.3));
+        }
+        assert!(close(route.delay(), 2.2));
+
+        // After the first contact of the source ends, only the slower route remains
+        let route = plan.earliest_arrival(0, 1, 10.0).unwrap();
+        assert!(close(route.arrival(), 20.1));
+        assert_eq!(route.hops()[0].to, RouteNode::Satellite(2));
+
+        assert!(plan.earliest_arrival(0, 1, 100.0).is_none());
+    }
+}
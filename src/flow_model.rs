@@ -0,0 +1,386 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use rayon::prelude::*;
+
+use crate::{model::{Model, ConnectionGraph, GeoCoordinates, TrafficDemand}, routing::{Route, RoutingConfig, RoutingGraph}};
+
+/// Flow-level view of a traffic matrix routed through the topology: the
+/// traffic of every demand is split over one or more routes (according to
+/// the multipath mode of the routing configuration), and routes that share an
+/// overloaded link are scaled down proportionally.
+pub struct FlowAssignment {
+    /// Routes of each demand, with the traffic (in Gbps) they carry.
+    routes: Vec<Vec<(Route, f64)>>,
+    /// Offered load and capacity (both in Gbps) of every link used by the routes.
+    links: BTreeMap<(usize, usize), (f64, f64)>,
+    /// Traffic (in Gbps) through each satellite.
+    satellite_loads: Vec<f64>,
+    delivered: Vec<f64>,
+    total_volume: f64,
+}
+
+impl FlowAssignment {
+    pub fn new(model: &Model, topology: &ConnectionGraph, config: &RoutingConfig, demands: &[TrafficDemand]) -> Self {
+        let sites: Vec<_> = demands.iter().flat_map(|d| [*d.source(), *d.destination()]).collect();
+        let graph = RoutingGraph::new(model, topology, config, &sites);
+
+        let routes: Vec<Vec<(Route, f64)>> = demands.par_iter().enumerate().map(|(i, d)| {
+            graph.multipath_routes(2 * i, 2 * i + 1).into_iter().map(|(r, fraction)| (r, fraction * d.volume())).collect()
+        }).collect();
+
+        let mut links = BTreeMap::new();
+        let mut satellite_loads = vec![0.0; model.satellites().len()];
+        for (route, volume) in routes.iter().flatten() {
+            for (a, b) in route.satellite_links() {
+                let capacity = topology.edge_weight(a, b).unwrap().capacity;
+                links.entry((a.min(b), a.max(b))).or_insert((0.0, capacity)).0 += volume;
+            }
+            for sat in route.satellites() {
+                satellite_loads[sat] += volume;
+            }
+        }
+
+        let delivered = routes.iter().map(|demand_routes| {
+            demand_routes.iter().map(|(route, volume)| {
+                let fraction = route.satellite_links()
+                    .map(|(a, b)| links[&(a.min(b), a.max(b))])
+                    .map(|(load, capacity)| (capacity / load).min(1.0))
+                    .fold(1.0, f64::min);
+                volume * fraction
+            }).sum()
+        }).collect();
+
+        FlowAssignment {
+            routes,
+            links,
+            satellite_loads,
+            delivered,
+            total_volume: demands.iter().map(TrafficDemand::volume).sum(),
+        }
+    }
+
+    /// Routes of each demand (empty if unreachable), with the traffic (in
+    /// Gbps) they carry.
+    pub fn routes(&self) -> &[Vec<(Route, f64)>] {
+        &self.routes
+    }
+
+    /// Traffic (in Gbps) delivered for each demand.
+    pub fn delivered(&self) -> &[f64] {
+        &self.delivered
+    }
+
+    /// Average number of routes used by the demands that can be routed.
+    pub fn path_diversity(&self) -> f64 {
+        let routed = self.routes.iter().filter(|r| !r.is_empty());
+        let (count, total) = routed.fold((0, 0), |(count, total), r| (count + 1, total + r.len()));
+
+        if count > 0 { total as f64 / count as f64 } else { 0.0 }
+    }
+
+    /// Offered load (in Gbps) of every link used by the demands.
+    pub fn link_loads(&self) -> impl Iterator<Item = ((usize, usize), f64)> + '_ {
+        self.links.iter().map(|(&link, &(load, _))| (link, load))
+    }
+
+    /// Jain's fairness index of the loads of the links used by the demands,
+    /// between `1 / n` (all traffic on a single link) and 1 (evenly spread).
+    pub fn load_fairness(&self) -> f64 {
+        let sum: f64 = self.link_loads().map(|(_, l)| l).sum();
+        let sum_squares: f64 = self.link_loads().map(|(_, l)| l * l).sum();
+
+        if sum_squares > 0.0 { sum * sum / (self.links.len() as f64 * sum_squares) } else { 1.0 }
+    }
+
+    /// Fraction of the total volume of the demands that traverses each
+    /// satellite (betweenness weighted by the traffic matrix).
+    pub fn satellite_betweenness(&self) -> Vec<f64> {
+        self.satellite_loads.iter().map(|l| if self.total_volume > 0.0 { l / self.total_volume } else { 0.0 }).collect()
+    }
+
+    /// Ratio between the offered load and the capacity of every link used by
+    /// the demands, which exceeds 1 for overloaded links.
+    pub fn utilization(&self) -> impl Iterator<Item = ((usize, usize), f64)> + '_ {
+        self.links.iter().map(|(&link, &(load, capacity))| (link, load / capacity))
+    }
+
+    pub fn max_utilization(&self) -> f64 {
+        self.utilization().map(|(_, u)| u).fold(0.0, f64::max)
+    }
+
+    /// Fraction of the total volume of the demands that is delivered.
+    pub fn satisfied_fraction(&self) -> f64 {
+        if self.total_volume > 0.0 {
+            self.delivered.iter().sum::<f64>() / self.total_volume
+        } else {
+            1.0
+        }
+    }
+
+    /// Links whose offered load exceeds their capacity.
+    pub fn bottlenecks(&self) -> Vec<(usize, usize)> {
+        self.utilization().filter(|(_, u)| *u > 1.0).map(|(link, _)| link).collect()
+    }
+}
+
+/// Arc of a flow network, stored alongside its reverse arc (at index `self ^ 1`).
+struct FlowArc {
+    to: usize,
+    capacity: f64,
+    residual: f64,
+}
+
+/// Directed network with arc capacities, on which the maximum flow between
+/// two nodes is calculated with Dinic's algorithm.
+struct FlowNetwork {
+    arcs: Vec<FlowArc>,
+    outgoing: Vec<Vec<usize>>,
+}
+
+impl FlowNetwork {
+    /// Residual capacities below this value are treated as zero.
+    const EPSILON: f64 = 1e-9;
+
+    fn new(num_nodes: usize) -> Self {
+        FlowNetwork { arcs: Vec::new(), outgoing: vec![Vec::new(); num_nodes] }
+    }
+
+    fn add_arc(&mut self, from: usize, to: usize, capacity: f64) {
+        self.outgoing[from].push(self.arcs.len());
+        self.arcs.push(FlowArc { to, capacity, residual: capacity });
+        self.outgoing[to].push(self.arcs.len());
+        self.arcs.push(FlowArc { to: from, capacity: 0.0, residual: 0.0 });
+    }
+
+    /// Removes any flow, so that another maximum flow can be calculated.
+    fn reset(&mut self) {
+        for arc in &mut self.arcs {
+            arc.residual = arc.capacity;
+        }
+    }
+
+    /// Nodes reachable from the source in the residual network, which form
+    /// the source side of a minimum cut once the maximum flow is calculated.
+    fn source_side(&self, source: usize) -> Vec<bool> {
+        let mut reachable = vec![false; self.outgoing.len()];
+        let mut stack = vec![source];
+        reachable[source] = true;
+
+        while let Some(node) = stack.pop() {
+            for &arc in &self.outgoing[node] {
+                let to = self.arcs[arc].to;
+                if self.arcs[arc].residual > Self::EPSILON && !reachable[to] {
+                    reachable[to] = true;
+                    stack.push(to);
+                }
+            }
+        }
+
+        reachable
+    }
+
+    /// Distance (in arcs) of every node from the source in the residual
+    /// network, if the sink is reachable. Nodes farther than the sink are not
+    /// labeled, since they cannot be part of a shortest augmenting path.
+    fn levels(&self, source: usize, sink: usize) -> Option<Vec<usize>> {
+        let mut levels = vec![usize::MAX; self.outgoing.len()];
+        let mut queue = VecDeque::from([source]);
+        levels[source] = 0;
+
+        while let Some(node) = queue.pop_front() {
+            if levels[node] >= levels[sink] {
+                break;
+            }
+            for &arc in &self.outgoing[node] {
+                let to = self.arcs[arc].to;
+                if self.arcs[arc].residual > Self::EPSILON && levels[to] == usize::MAX {
+                    levels[to] = levels[node] + 1;
+                    queue.push_back(to);
+                }
+            }
+        }
+
+        (levels[sink] != usize::MAX).then_some(levels)
+    }
+
+    /// Pushes flow along a path of increasing levels, returning how much was pushed.
+    fn augment(&mut self, node: usize, sink: usize, limit: f64, levels: &[usize], next_arc: &mut [usize]) -> f64 {
+        if node == sink {
+            return limit;
+        }
+
+        while next_arc[node] < self.outgoing[node].len() {
+            let arc = self.outgoing[node][next_arc[node]];
+            let to = self.arcs[arc].to;
+
+            if self.arcs[arc].residual > Self::EPSILON && levels[to] == levels[node] + 1 {
+                let pushed = self.augment(to, sink, limit.min(self.arcs[arc].residual), levels, next_arc);
+                if pushed > Self::EPSILON {
+                    self.arcs[arc].residual -= pushed;
+                    self.arcs[arc ^ 1].residual += pushed;
+                    return pushed;
+                }
+            }
+
+            next_arc[node] += 1;
+        }
+
+        0.0
+    }
+
+    /// Calculates the maximum flow from the source to the sink, stopping once
+    /// it reaches a given limit.
+    fn max_flow(&mut self, source: usize, sink: usize, limit: f64) -> f64 {
+        let mut flow = 0.0;
+
+        while flow < limit - Self::EPSILON {
+            let levels = match self.levels(source, sink) {
+                Some(levels) => levels,
+                None => break,
+            };
+
+            let mut next_arc = vec![0; self.outgoing.len()];
+            while flow < limit - Self::EPSILON {
+                let pushed = self.augment(source, sink, limit - flow, &levels, &mut next_arc);
+                if pushed <= Self::EPSILON {
+                    break;
+                }
+                flow += pushed;
+            }
+        }
+
+        flow
+    }
+}
+
+/// Calculates the maximum traffic (in Gbps) that the topology can carry from
+/// a set of ground stations to another. Ground stations can exchange traffic
+/// with every satellite in view, limited by the capacity of the satellite's
+/// terminals, but do not relay traffic themselves.
+pub fn max_flow(model: &Model, topology: &ConnectionGraph, sources: &[GeoCoordinates], destinations: &[GeoCoordinates]) -> f64 {
+    let num_satellites = model.satellites().len();
+    let num_stations = sources.len() + destinations.len();
+    let (source, sink) = (num_satellites + num_stations, num_satellites + num_stations + 1);
+
+    let mut network = FlowNetwork::new(num_satellites + num_stations + 2);
+
+    for (a, b, link) in topology.all_edges() {
+        network.add_arc(a, b, link.capacity);
+        network.add_arc(b, a, link.capacity);
+    }
+
+    let stations = sources.iter().map(|c| (c, true)).chain(destinations.iter().map(|c| (c, false)));
+    for (i, (coordinates, is_source)) in stations.enumerate() {
+        let station = num_satellites + i;
+        let point = model.surface_point(coordinates);
+
+        if is_source {
+            network.add_arc(source, station, f64::INFINITY);
+        } else {
+            network.add_arc(station, sink, f64::INFINITY);
+        }
+
+        for sat in topology.nodes().map(|id| &model.satellites()[id]).filter(|s| s.is_in_view_cone(&point)) {
+            if is_source {
+                network.add_arc(station, sat.id(), sat.link_capacity());
+            } else {
+                network.add_arc(sat.id(), station, sat.link_capacity());
+            }
+        }
+    }
+
+    network.max_flow(source, sink, f64::INFINITY)
+}
+
+/// Network in which the maximum flow between two satellites is the number of
+/// vertex-disjoint paths between them in the topology. Every satellite is
+/// split into an input node (`2 * id`) and an output node (`2 * id + 1`)
+/// joined by an arc of unit capacity, and links join the output node of each
+/// satellite to the input node of the other with unlimited capacity, so that
+/// minimum cuts only contain satellites.
+pub struct VertexDisjointPaths {
+    network: FlowNetwork,
+}
+
+impl VertexDisjointPaths {
+    pub fn new(topology: &ConnectionGraph, num_satellites: usize) -> Self {
+        let mut network = FlowNetwork::new(2 * num_satellites);
+
+        for id in topology.nodes() {
+            network.add_arc(2 * id, 2 * id + 1, 1.0);
+        }
+        for (a, b, _) in topology.all_edges() {
+            network.add_arc(2 * a + 1, 2 * b, f64::INFINITY);
+            network.add_arc(2 * b + 1, 2 * a, f64::INFINITY);
+        }
+
+        VertexDisjointPaths { network }
+    }
+
+    /// Returns a set of fewer than `k` satellites whose removal separates two
+    /// non-adjacent satellites, if there are fewer than `k` vertex-disjoint
+    /// paths between them.
+    pub fn small_cut(&mut self, a: usize, b: usize, k: usize) -> Option<Vec<usize>> {
+        self.network.reset();
+
+        let paths = self.network.max_flow(2 * a + 1, 2 * b, k as f64);
+        if paths > k as f64 - 0.5 {
+            return None;
+        }
+
+        // Satellites whose input node is on the source side of the minimum cut but not their output node
+        let side = self.network.source_side(2 * a + 1);
+        Some((0..side.len() / 2).filter(|&id| side[2 * id] && !side[2 * id + 1]).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use petgraph::graphmap::GraphMap;
+
+    use super::*;
+    use crate::model::Link;
+
+    fn clrs_network() -> FlowNetwork {
+        let mut network = FlowNetwork::new(6);
+        for (from, to, capacity) in [(0, 1, 16.0), (0, 2, 13.0), (1, 3, 12.0), (2, 1, 4.0), (2, 4, 14.0), (3, 2, 9.0), (3, 5, 20.0), (4, 3, 7.0), (4, 5, 4.0)] {
+            network.add_arc(from, to, capacity);
+        }
+        network
+    }
+
+    #[test]
+    fn max_flow_matches_min_cut() {
+        let mut network = clrs_network();
+        assert!((network.max_flow(0, 5, f64::INFINITY) - 23.0).abs() < 1e-9);
+
+        // The minimum cut separates {0, 1, 2, 4} from {3, 5}
+        let side = network.source_side(0);
+        assert_eq!(side, vec![true, true, true, false, true, false]);
+    }
+
+    #[test]
+    fn max_flow_stops_at_limit() {
+        let mut network = clrs_network();
+        assert!((network.max_flow(0, 5, 10.0) - 10.0).abs() < 1e-9);
+
+        network.reset();
+        assert!((network.max_flow(0, 5, f64::INFINITY) - 23.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn small_cut_in_ring() {
+        let mut topology: ConnectionGraph = GraphMap::new();
+        for id in 0..6 {
+            topology.add_edge(id, (id + 1) % 6, Link { length: 1.0, capacity: 1.0 });
+        }
+        let mut paths = VertexDisjointPaths::new(&topology, 6);
+
+        // Opposite satellites of a ring are joined by exactly two vertex-disjoint paths
+        assert_eq!(paths.small_cut(0, 3, 2), None);
+        let cut = paths.small_cut(0, 3, 3).unwrap();
+        assert_eq!(cut.len(), 2);
+        assert!(cut.iter().any(|id| [1, 2].contains(id)));
+        assert!(cut.iter().any(|id| [4, 5].contains(id)));
+    }
+}
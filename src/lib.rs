@@ -0,0 +1,11 @@
+pub mod config;
+pub mod connection_strategy;
+pub mod contact_plan;
+pub mod flow_model;
+pub mod link_state;
+pub mod model;
+pub mod packet_simulation;
+pub mod routing;
+pub mod server;
+pub mod statistics;
+pub mod strategy_registry;
@@ -0,0 +1,332 @@
+use std::collections::{BTreeMap, BinaryHeap, HashSet, VecDeque};
+
+use crate::{model::LIGHT_SPEED, routing::{RoutingGraph, SearchState}};
+
+/// Timing parameters of the emulated link-state routing protocol.
+#[derive(Clone)]
+pub struct LinkStateConfig {
+    /// Time (in seconds) a satellite takes to detect that one of its links
+    /// was established or lost.
+    detection_delay: f64,
+    /// Time (in seconds) each satellite takes to process and forward a
+    /// link-state advertisement while it is flooded.
+    processing_delay: f64,
+    /// Time (in seconds) between receiving an advertisement and installing
+    /// the recalculated routing table.
+    spf_delay: f64,
+}
+
+impl LinkStateConfig {
+    pub fn new(detection_delay: f64, processing_delay: f64, spf_delay: f64) -> Self {
+        assert!(detection_delay >= 0.0 && processing_delay >= 0.0 && spf_delay >= 0.0);
+
+        LinkStateConfig { detection_delay, processing_delay, spf_delay }
+    }
+}
+
+/// Next hop of every node (satellites and gateways) along its shortest path
+/// towards each gateway.
+pub struct RoutingTables {
+    next_hops: Vec<Vec<Option<usize>>>,
+}
+
+impl RoutingTables {
+    pub fn new(graph: &RoutingGraph, num_gateways: usize) -> Self {
+        RoutingTables {
+            next_hops: (0..num_gateways).map(|g| graph.gateway_next_hops(g)).collect(),
+        }
+    }
+
+    /// Next hop from a node towards a gateway (`None` if the gateway is
+    /// unreachable).
+    pub fn next_hop(&self, node: usize, gateway: usize) -> Option<usize> {
+        self.next_hops[gateway][node]
+    }
+}
+
+/// Emulation of a link-state routing protocol. When the topology changes, the
+/// satellites next to the change detect it and flood advertisements through
+/// the remaining links; each satellite keeps forwarding with its previous
+/// routing table until it receives the advertisement and recalculates its
+/// table. Meanwhile, some routes may be stale (lead to lost links or loops).
+pub struct LinkStateProtocol {
+    config: LinkStateConfig,
+    num_satellites: usize,
+    num_gateways: usize,
+    /// Routing tables of every version of the topology that is still in use.
+    tables: BTreeMap<u64, RoutingTables>,
+    latest_version: u64,
+    /// Links (in both directions) of the latest topology.
+    links: HashSet<(usize, usize)>,
+    alive: Vec<bool>,
+    /// Version of the routing table installed in each satellite.
+    installed: Vec<u64>,
+    /// Times at which each satellite installs more recent routing tables, in
+    /// chronological order.
+    pending: Vec<VecDeque<(f64, u64)>>,
+    convergence_time: Option<f64>,
+    stale_routes: usize,
+    stale_since: Option<f64>,
+    last_stale_window: Option<f64>,
+}
+
+impl LinkStateProtocol {
+    pub fn new(config: LinkStateConfig, num_satellites: usize, num_gateways: usize) -> Self {
+        LinkStateProtocol {
+            config,
+            num_satellites,
+            num_gateways,
+            tables: BTreeMap::new(),
+            latest_version: 0,
+            links: HashSet::new(),
+            alive: vec![true; num_satellites],
+            installed: vec![0; num_satellites],
+            pending: vec![VecDeque::new(); num_satellites],
+            convergence_time: None,
+            stale_routes: 0,
+            stale_since: None,
+            last_stale_window: None,
+        }
+    }
+
+    /// Time (in seconds) that the most recent topology change took to
+    /// propagate to every affected satellite.
+    pub fn convergence_time(&self) -> Option<f64> {
+        self.convergence_time
+    }
+
+    /// Number of satellites that still use an outdated routing table.
+    pub fn stale_tables(&self) -> usize {
+        (0..self.num_satellites).filter(|&s| self.alive[s] && self.installed[s] != self.latest_version).count()
+    }
+
+    /// Number of (satellite, gateway) pairs for which the latest topology has
+    /// a route, but the installed routing tables fail to deliver traffic.
+    pub fn stale_routes(&self) -> usize {
+        self.stale_routes
+    }
+
+    /// Duration (in seconds) of the most recent period during which some
+    /// routes were stale.
+    pub fn last_stale_window(&self) -> Option<f64> {
+        self.last_stale_window
+    }
+
+    /// Routing table entry of a satellite towards a gateway, as currently
+    /// installed in the satellite.
+    pub fn next_hop(&self, sat: usize, gateway: usize) -> Option<usize> {
+        self.tables[&self.installed[sat]].next_hop(sat, gateway)
+    }
+
+    /// Notifies the protocol of a new topology at time `t`. The satellites in
+    /// `origins` (whose links changed) detect the change and flood it.
+    pub fn topology_changed(&mut self, graph: &RoutingGraph, alive: Vec<bool>, origins: &[usize], t: f64) {
+        let first_version = self.tables.is_empty();
+
+        self.latest_version += 1;
+        self.tables.insert(self.latest_version, RoutingTables::new(graph, self.num_gateways));
+        self.links = graph.links().map(|(a, b, _)| (a, b)).collect();
+        self.alive = alive;
+
+        if first_version {
+            self.installed.fill(self.latest_version);
+        } else {
+            let arrivals = self.flooding_delays(graph, origins);
+            self.convergence_time = arrivals.iter().flatten().map(|d| d + self.config.spf_delay).reduce(f64::max);
+
+            // Satellites that the advertisements cannot reach are not affected by the change
+            for (sat, arrival) in arrivals.into_iter().enumerate() {
+                let pending = &mut self.pending[sat];
+                match (arrival, pending.back_mut()) {
+                    (Some(delay), _) => {
+                        // Earlier installs remain in place, while later ones are superseded
+                        let time = t + delay + self.config.spf_delay;
+                        pending.retain(|&(install_time, _)| install_time < time);
+                        pending.push_back((time, self.latest_version));
+                    },
+                    (None, Some((_, version))) => *version = self.latest_version,
+                    (None, None) => self.installed[sat] = self.latest_version,
+                }
+            }
+        }
+
+        self.update_stale_routes(t);
+        self.remove_unused_tables();
+    }
+
+    /// Installs the routing tables received up to a given time.
+    pub fn advance(&mut self, until: f64) {
+        let mut installs: Vec<(f64, usize, u64)> = self.pending.iter().enumerate()
+            .flat_map(|(sat, p)| p.iter().take_while(|(t, _)| *t <= until).map(move |&(t, version)| (t, sat, version)))
+            .collect();
+        installs.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        for (i, &(t, sat, version)) in installs.iter().enumerate() {
+            self.installed[sat] = version;
+            self.pending[sat].pop_front();
+
+            // Evaluate the routes once every table installed at the same time is in place
+            if installs.get(i + 1).is_none_or(|next| next.0 > t) {
+                self.update_stale_routes(t);
+            }
+        }
+
+        self.remove_unused_tables();
+    }
+
+    /// Delay until each satellite receives the advertisements flooded by the
+    /// satellites next to a change (`None` if never received).
+    fn flooding_delays(&self, graph: &RoutingGraph, origins: &[usize]) -> Vec<Option<f64>> {
+        let mut adjacency = vec![Vec::new(); self.num_satellites];
+        for (a, b, length) in graph.links().filter(|&(a, b, _)| a < self.num_satellites && b < self.num_satellites) {
+            adjacency[a].push((b, length));
+        }
+
+        let mut delays = vec![f64::INFINITY; self.num_satellites];
+        let mut queue = BinaryHeap::new();
+
+        for &origin in origins.iter().filter(|&&o| self.alive[o]) {
+            delays[origin] = self.config.detection_delay;
+            queue.push(SearchState { estimate: self.config.detection_delay, node: origin });
+        }
+
+        while let Some(SearchState { estimate, node }) = queue.pop() {
+            if estimate > delays[node] {
+                continue;
+            }
+
+            for &(neighbor, length) in &adjacency[node] {
+                let delay = delays[node] + self.config.processing_delay + length / LIGHT_SPEED;
+                if delay < delays[neighbor] {
+                    delays[neighbor] = delay;
+                    queue.push(SearchState { estimate: delay, node: neighbor });
+                }
+            }
+        }
+
+        delays.into_iter().map(|d| Some(d).filter(|d| d.is_finite())).collect()
+    }
+
+    /// Counts the stale routes at time `t`, keeping track of the periods
+    /// during which there are stale routes.
+    fn update_stale_routes(&mut self, t: f64) {
+        self.stale_routes = (0..self.num_gateways).map(|g| self.count_stale_routes(g)).sum();
+
+        match (self.stale_routes, self.stale_since) {
+            (0, Some(since)) => {
+                self.last_stale_window = Some(t - since);
+                self.stale_since = None;
+            },
+            (n, None) if n > 0 => self.stale_since = Some(t),
+            _ => {},
+        }
+    }
+
+    /// Follows the installed routing tables from every satellite towards a
+    /// gateway, counting the satellites whose traffic would not arrive.
+    fn count_stale_routes(&self, gateway: usize) -> usize {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State { Unknown, Visiting, Delivered, Lost }
+
+        let latest = &self.tables[&self.latest_version];
+        let target = self.num_satellites + gateway;
+
+        // Gateways are part of the ground infrastructure, whose tables are always up to date
+        let next_hop = |node: usize| match node < self.num_satellites {
+            true => self.tables[&self.installed[node]].next_hop(node, gateway),
+            false => latest.next_hop(node, gateway),
+        };
+
+        let mut states = vec![State::Unknown; latest.next_hops[gateway].len()];
+        states[target] = State::Delivered;
+
+        let mut stale = 0;
+        for sat in (0..self.num_satellites).filter(|&s| self.alive[s] && latest.next_hop(s, gateway).is_some()) {
+            let mut path = Vec::new();
+            let mut node = sat;
+
+            let state = loop {
+                match states[node] {
+                    State::Unknown => {},
+                    State::Visiting => break State::Lost,
+                    known => break known,
+                }
+
+                states[node] = State::Visiting;
+                path.push(node);
+
+                match next_hop(node) {
+                    Some(next) if self.links.contains(&(node, next)) => node = next,
+                    _ => break State::Lost,
+                }
+            };
+
+            for node in path {
+                states[node] = state;
+            }
+            if state == State::Lost {
+                stale += 1;
+            }
+        }
+
+        stale
+    }
+
+    fn remove_unused_tables(&mut self) {
+        let latest = self.latest_version;
+        let used: HashSet<u64> = self.installed.iter().chain(self.pending.iter().flatten().map(|(_, v)| v)).copied().collect();
+
+        self.tables.retain(|version, _| *version == latest || used.contains(version));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{connection_strategy::{ConnectionStrategy, GridStrategy}, model::{ConstellationType, GeoCoordinates, Model, EARTH_RADIUS}, routing::{Gateway, RoutingConfig}};
+
+    #[test]
+    fn removed_link_makes_routes_stale_until_convergence() {
+        let model = Model::new(6, 8, 53f64.to_radians(), ConstellationType::Delta, 1, EARTH_RADIUS + 4e6, 4);
+        let mut topology = GridStrategy::new(1, None, true).run(&model);
+        let config = RoutingConfig::new(vec![Gateway::new("Madrid", GeoCoordinates::new(40.4, -3.7))], 0.0);
+        let num_satellites = model.satellites().len();
+
+        let mut protocol = LinkStateProtocol::new(LinkStateConfig::new(0.01, 0.001, 0.05), num_satellites, 1);
+        let graph = RoutingGraph::new(&model, &topology, &config, &[]);
+        protocol.topology_changed(&graph, vec![true; num_satellites], &[], 0.0);
+        assert_eq!(protocol.stale_routes(), 0);
+
+        // Remove a link between satellites towards the gateway
+        let (a, b) = (0..num_satellites)
+            .find_map(|s| protocol.next_hop(s, 0).filter(|&n| n < num_satellites).map(|n| (s, n)))
+            .unwrap();
+        topology.remove_edge(a, b);
+
+        // Traffic following the previous tables is lost if it goes through the removed link
+        let gateway = graph.gateway_node(0);
+        let uses_link = |mut node: usize| {
+            while node != gateway {
+                let next = protocol.next_hop(node, 0).unwrap();
+                if (node, next) == (a, b) {
+                    return true;
+                }
+                node = next;
+            }
+            false
+        };
+        let expected = (0..num_satellites).filter(|&s| uses_link(s)).count();
+
+        let graph = RoutingGraph::new(&model, &topology, &config, &[]);
+        protocol.topology_changed(&graph, vec![true; num_satellites], &[a, b], 1.0);
+        assert!(expected > 0);
+        assert_eq!(protocol.stale_routes(), expected);
+        assert_eq!(protocol.stale_tables(), num_satellites);
+
+        let convergence_time = protocol.convergence_time().unwrap();
+        protocol.advance(1.0 + convergence_time);
+        assert_eq!(protocol.stale_routes(), 0);
+        assert_eq!(protocol.stale_tables(), 0);
+        assert!(protocol.last_stale_window().is_some_and(|w| w > 0.0 && w <= convergence_time));
+    }
+}
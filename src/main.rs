@@ -1,15 +1,10 @@
 
 use std::{fs::{self, File}, env, path::Path, net::{TcpListener, TcpStream, SocketAddrV4, Ipv4Addr}, sync::Arc, sync::{Mutex, mpsc::{self, Receiver}}, thread, time::Duration, io::{self, Write, Read}};
-use connection_strategy::{ConnectionStrategy, GridStrategy};
+use iscs::config::Config;
 
-use connection_strategy::NearestNeighborStrategy;
-use model::{EARTH_RADIUS, Simulation, Model, ConstellationType};
-use server::{init_msg, update_msg};
-
-pub mod connection_strategy;
-pub mod model;
-pub mod server;
-pub mod statistics;
+use iscs::model::Simulation;
+use iscs::server::{init_msg, update_msg};
+use iscs::strategy_registry::StrategyRegistry;
 
 const SERVER_PORT: u16 = 2000;
 const STATISTICS_PORT: u16 = 2001;
@@ -17,153 +12,36 @@ const STATISTICS_PORT: u16 = 2001;
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    // Constellation parameters
-    let orbiting_altitude: f64;
-    let num_orbital_planes: usize;
-    let satellites_per_plane: usize;
-    let inclination: f64;
-    let max_connections: usize;
-
-    let constellation_type: ConstellationType;
-    let phasing: i64;
-
-    // Simulation parameters
-    let file_path: Option<String>;
-    let steps: Option<usize>;
-
-    let simulation_speed: f64;
-    let update_frequency: f64;
-    let update_frequency_server: f64;
-    let connection_refresh_interval: f64;
-
-    let rng_seed: Option<u64>;
-    let starting_failure_probability: f64;
-    let recurrent_failure_probability: f64;
-
-    let strategy: Box<dyn ConnectionStrategy>;
-
-    if args.len() == 1 {
-        orbiting_altitude = 0.55e6;
-        num_orbital_planes = 12;
-        satellites_per_plane = 30;
-        inclination = 60.0;
-        max_connections = 4;
-
-        constellation_type = ConstellationType::Delta;
-        phasing = 0;
-
-        file_path = None;
-        steps = None;
-
-        simulation_speed = 1.0;
-        update_frequency = 10.0;
-        update_frequency_server = update_frequency;
-        connection_refresh_interval = 10.0;
-
-        rng_seed = None;
-        starting_failure_probability = 0.0;
-        recurrent_failure_probability = 0.0;
-
-        strategy = Box::new(GridStrategy::new(0));
+    let config = if args.len() == 1 {
+        Config::default()
     } else if args.len() == 2 {
-        use toml::Value;
-        use toml::map::Map;
-
-        let empty_parameters = Map::new();
-
         let path = Path::new(&args[1]);
         if !path.exists() {
             panic!("Specified path does not exist!");
         }
-        let contents = fs::read_to_string(path).expect("Error when reading config file!")
-            .parse::<Value>().expect("Error when parsing config file!");
-
-        let constellation_parameters = match &contents["constellation"] {
-            Value::Table(t) => t,
-            _ => panic!(),
-        };
-
-        let simulation_parameters = match &contents.get("simulation") {
-            Some(Value::Table(t)) => t,
-            _ => &empty_parameters,
-        };
-
-        orbiting_altitude    = constellation_parameters["altitude"]            .as_float()  .unwrap();
-        num_orbital_planes   = constellation_parameters["num_orbital_planes"]  .as_integer().unwrap() as usize;
-        satellites_per_plane = constellation_parameters["satellites_per_plane"].as_integer().unwrap() as usize;
-        inclination          = constellation_parameters["inclination"]         .as_float()  .unwrap();
-        max_connections      = constellation_parameters["max_connections"]     .as_integer().unwrap() as usize;
-
-        constellation_type   = constellation_parameters.get("type")   .and_then(Value::as_str)
-            .and_then(|v| ConstellationType::try_from(v).ok())
-            .unwrap_or(ConstellationType::Delta);
-        phasing              = constellation_parameters.get("phasing").and_then(Value::as_integer).unwrap_or(0); 
-        assert!((0..num_orbital_planes as i64).contains(&phasing));
-
-        file_path = simulation_parameters.get("file_path").and_then(Value::as_str).map(|s| s.to_owned());
-        steps     = simulation_parameters.get("steps")    .and_then(Value::as_integer).map(|v| v as usize);
-
-        if file_path.is_some() {
-            steps.expect("Must specify a finite number of time steps when saving simulation data to a file!");
-        }
-
-        simulation_speed            = simulation_parameters.get("simulation_speed")           .and_then(Value::as_float).unwrap_or(1.0);
-        update_frequency            = simulation_parameters.get("update_frequency")           .and_then(Value::as_float).unwrap_or(10.0);
-        update_frequency_server     = simulation_parameters.get("update_frequency_server")    .and_then(Value::as_float).unwrap_or(update_frequency);
-        connection_refresh_interval = simulation_parameters.get("connection_refresh_interval").and_then(Value::as_float).unwrap_or(10.0);
-
-        rng_seed                      = simulation_parameters.get("rng_seed")                     .and_then(Value::as_integer).map(|v| v as u64);
-        starting_failure_probability  = simulation_parameters.get("starting_failure_probability") .and_then(Value::as_float).unwrap_or(0.0);
-        recurrent_failure_probability = simulation_parameters.get("recurrent_failure_probability").and_then(Value::as_float).unwrap_or(0.0);
-        assert!((0.0..=1.0).contains(&recurrent_failure_probability));
-        assert!((0.0..=1.0).contains(&starting_failure_probability));
-
-        strategy = match &contents.get("strategy") {
-            Some(Value::Table(params)) => {
-                match params["type"].as_str().unwrap() {
-                    "grid" => {
-                        let offset = params.get("offset").and_then(Value::as_integer).unwrap_or(0) as usize;
-                        Box::new(GridStrategy::new(offset))
-                    },
-                    "nearest_neighbor" => Box::new(NearestNeighborStrategy::new()),
-                    _ => panic!("Invalid strategy type."),
-                }
-            }
-            _ => Box::new(GridStrategy::new(0)),
-        }
+        let contents = fs::read_to_string(path).expect("Error when reading config file!");
+        Config::parse(&contents).unwrap_or_else(|e| panic!("{}", e))
     } else {
         panic!("More than one argument!");
+    };
+
+    // Results do not depend on the number of threads, which defaults to the number of CPUs
+    if let Some(threads) = config.threads {
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global().expect("Couldn't create the thread pool!");
     }
 
     let (sender, receiver) = mpsc::channel();
 
-    let sim = Arc::new(Mutex::new(Simulation::new(
-        Model::new(
-            num_orbital_planes,
-            satellites_per_plane,
-            inclination.to_radians(),
-            constellation_type,
-            phasing as usize,
-            EARTH_RADIUS + orbiting_altitude,
-            max_connections,
-        ),
-        simulation_speed / update_frequency,
-        simulation_speed,
-        connection_refresh_interval,
-        rng_seed,
-        starting_failure_probability,
-        recurrent_failure_probability,
-        strategy,
-        sender,
-    )));
-
-    let mut delay = Duration::from_secs_f64(1.0 / update_frequency);
-    let delay_server = Duration::from_secs_f64(1.0 / update_frequency_server);
+    let simulation = config.build_simulation(&StrategyRegistry::default(), sender).unwrap_or_else(|e| panic!("{}", e));
+    let sim = Arc::new(Mutex::new(simulation));
+
+    let mut delay = Duration::from_secs_f64(1.0 / config.update_frequency);
+    let delay_server = Duration::from_secs_f64(1.0 / config.update_frequency_server);
 
     let mut server_handle = None;
     let statistics_handle;
 
-    if let Some(file_path) = file_path {
+    if let Some(file_path) = config.file_path {
         let mut file = File::create(file_path).expect("Couldn't create file!");
         statistics_handle = thread::spawn(move || { file_thread(receiver, &mut file) });
         // Run simulation without artificial delays (only collect data)
@@ -175,7 +53,7 @@ fn main() {
         statistics_handle = thread::spawn(move || { statistics_thread(receiver) });
     }
 
-    let simulation_handle = thread::spawn(move || { simulation_thread(sim, steps, delay) });
+    let simulation_handle = thread::spawn(move || { simulation_thread(sim, config.steps, delay) });
     simulation_handle.join().expect("Couldn't join simulation thread.");
 
     let _ = statistics_handle.join().expect("Couldn't join statistics thread.");
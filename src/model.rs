@@ -1,10 +1,11 @@
-use std::{f64::consts::PI, sync::{Arc, mpsc::Sender}};
+use std::{collections::HashSet, f64::consts::PI, sync::{Arc, mpsc::Sender}};
 
 use nalgebra::{Rotation3, Vector3};
-use petgraph::{algo::astar, graphmap::GraphMap, Undirected, visit::EdgeRef};
+use petgraph::{graphmap::GraphMap, Undirected};
 use rand::{Rng, rngs::StdRng, SeedableRng};
+use rayon::prelude::*;
 
-use crate::{connection_strategy::ConnectionStrategy, statistics::statistics_msg};
+use crate::{contact_plan::{ContactPlan, ContactPlanConfig, ContactRoute}, flow_model::{self, FlowAssignment}, link_state::{LinkStateConfig, LinkStateProtocol}, packet_simulation::{PacketConfig, PacketSimulation, PacketStatistics}, connection_strategy::{ConnectionStrategy, StrategyContext, TopologyUpdate}, routing::{self, Disjointness, Route, RoutingConfig, RoutingGraph}, statistics::{statistics_msg, PathHistory}};
 
 /// Earth's standard gravitational parameter (gravitational constant times the Earth's mass).
 pub const GM: f64 = 3.986004418e14;
@@ -14,7 +15,10 @@ pub const EARTH_RADIUS: f64 = 6.371e6;
 pub const EARTH_ROTATION_PERIOD: f64 = 86400.0;
 /// Speed of light, in meters per second.
 pub const LIGHT_SPEED: f64 = 299792458.0;
+/// Default data rate of inter-satellite link terminals, in Gbps.
+pub const DEFAULT_LINK_CAPACITY: f64 = 10.0;
 
+#[derive(Clone, Copy, PartialEq)]
 pub struct GeoCoordinates {
     latitude: f64,
     longitude: f64,
@@ -51,6 +55,143 @@ impl GeoCoordinates {
     }
 }
 
+/// Returns true if the segment between two points is not obstructed by the Earth.
+pub fn has_line_of_sight(from: &Vector3<f64>, to: &Vector3<f64>) -> bool {
+    let distance_to_point = from.metric_distance(to);
+    let segment_range = 0.0..distance_to_point;
+    let direction = (to - from).normalize();
+
+    let d = -direction.dot(from);
+    let nabla = direction.dot(from).powi(2) - from.norm_squared() + EARTH_RADIUS.powi(2);
+
+    if nabla < 0.0 {
+        true
+    }
+    else if nabla == 0.0 {
+        !segment_range.contains(&d)
+    }
+    else {
+        let nabla = nabla.sqrt();
+        !(segment_range.contains(&(d - nabla)) || segment_range.contains(&(d + nabla)))
+    }
+}
+
+/// Pair of locations between which routing statistics are collected.
+#[derive(Clone)]
+pub struct CityPair {
+    name: String,
+    source: GeoCoordinates,
+    destination: GeoCoordinates,
+}
+
+impl CityPair {
+    pub fn new(name: &str, source: GeoCoordinates, destination: GeoCoordinates) -> Self {
+        CityPair { name: name.to_owned(), source, destination }
+    }
+
+    /// Round trip times from London to other cities, collected by default.
+    pub fn defaults() -> Vec<CityPair> {
+        let london       = GeoCoordinates::new(51.507222, -0.1275);
+        let nyc          = GeoCoordinates::new(40.712778, -74.006111);
+        let johannesburg = GeoCoordinates::new(-26.204444, 28.045556);
+        let singapore    = GeoCoordinates::new(1.291667, 103.85);
+
+        vec![
+            CityPair::new("nyc"         , london, nyc         ),
+            CityPair::new("singapore"   , london, singapore   ),
+            CityPair::new("johannesburg", london, johannesburg),
+        ]
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &GeoCoordinates {
+        &self.source
+    }
+
+    pub fn destination(&self) -> &GeoCoordinates {
+        &self.destination
+    }
+}
+
+/// Pair of regions (sets of ground stations) between which the maximum flow
+/// of traffic is calculated.
+#[derive(Clone)]
+pub struct RegionPair {
+    name: String,
+    sources: Vec<GeoCoordinates>,
+    destinations: Vec<GeoCoordinates>,
+}
+
+impl RegionPair {
+    pub fn new(name: &str, sources: Vec<GeoCoordinates>, destinations: Vec<GeoCoordinates>) -> Self {
+        RegionPair { name: name.to_owned(), sources, destinations }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn sources(&self) -> &[GeoCoordinates] {
+        &self.sources
+    }
+
+    pub fn destinations(&self) -> &[GeoCoordinates] {
+        &self.destinations
+    }
+}
+
+/// Location included in the matrix of round trip times between ground sites.
+#[derive(Clone)]
+pub struct GroundSite {
+    name: String,
+    coordinates: GeoCoordinates,
+}
+
+impl GroundSite {
+    pub fn new(name: &str, coordinates: GeoCoordinates) -> Self {
+        GroundSite { name: name.to_owned(), coordinates }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn coordinates(&self) -> &GeoCoordinates {
+        &self.coordinates
+    }
+}
+
+/// Volume of traffic (in Gbps) between two locations.
+#[derive(Clone)]
+pub struct TrafficDemand {
+    source: GeoCoordinates,
+    destination: GeoCoordinates,
+    volume: f64,
+}
+
+impl TrafficDemand {
+    pub fn new(source: GeoCoordinates, destination: GeoCoordinates, volume: f64) -> Self {
+        assert!(volume >= 0.0);
+
+        TrafficDemand { source, destination, volume }
+    }
+
+    pub fn source(&self) -> &GeoCoordinates {
+        &self.source
+    }
+
+    pub fn destination(&self) -> &GeoCoordinates {
+        &self.destination
+    }
+
+    pub fn volume(&self) -> f64 {
+        self.volume
+    }
+}
+
 pub struct OrbitalPlane {
     id: usize,
     semimajor_axis: f64,
@@ -92,12 +233,15 @@ impl OrbitalPlane {
     }
 }
 
+#[derive(Clone)]
 pub struct Satellite {
     id: usize,
     orbital_plane: Arc<OrbitalPlane>,
     arg_periapsis: f64,
     position: Vector3<f64>,
     status: bool,
+    max_connections: usize,
+    link_capacity: f64,
 }
 
 impl Satellite {
@@ -108,6 +252,7 @@ impl Satellite {
         orbital_plane: Arc<OrbitalPlane>, 
         arg_periapsis: f64,
         status: bool,
+        max_connections: usize,
     ) -> Self {
         Satellite {
             id,
@@ -115,6 +260,8 @@ impl Satellite {
             arg_periapsis,
             position: Vector3::zeros(),
             status,
+            max_connections,
+            link_capacity: DEFAULT_LINK_CAPACITY,
         }
     }
 
@@ -142,18 +289,50 @@ impl Satellite {
         self.status = status;
     }
 
+    /// Maximum number of links that the satellite can establish (number of
+    /// inter-satellite link terminals). Satellites without terminals can only
+    /// relay traffic between ground locations.
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    pub fn set_max_connections(&mut self, max_connections: usize) {
+        self.max_connections = max_connections;
+    }
+
+    /// Data rate (in Gbps) of the inter-satellite link terminals.
+    pub fn link_capacity(&self) -> f64 {
+        self.link_capacity
+    }
+
+    pub fn set_link_capacity(&mut self, link_capacity: f64) {
+        assert!(link_capacity > 0.0);
+        self.link_capacity = link_capacity;
+    }
+
     pub fn recalculate_position(&mut self, t: f64) {
+        self.position = self.position_at(t);
+    }
+
+    /// Returns the position of the satellite at an arbitrary time, without
+    /// modifying its current position.
+    pub fn position_at(&self, t: f64) -> Vector3<f64> {
         let r = self.orbital_plane.semimajor_axis;
         let true_anomaly = (t * self.orbital_plane.angular_speed) % (2.0 * PI);
 
         let new_position = Vector3::new(r, 0.0, 0.0);
 
-        self.position = Rotation3::from_euler_angles(0.0, self.orbital_plane.longitude, 0.0) *
+        Rotation3::from_euler_angles(0.0, self.orbital_plane.longitude, 0.0) *
             Rotation3::from_euler_angles(self.orbital_plane.inclination, 0.0, 0.0) *
             Rotation3::from_euler_angles(0.0, self.arg_periapsis + true_anomaly, 0.0) *
             new_position
     }
 
+    /// Returns the latitude (in degrees) of the point directly below the satellite.
+    pub fn latitude(&self) -> f64 {
+        (self.position.y / self.position.norm()).asin().to_degrees()
+    }
+
     pub fn velocity(&self) -> Vector3<f64> {
         let direction = Rotation3::from_euler_angles(0.0, PI / 2.0, 0.0) * self.position.normalize();
 
@@ -163,28 +342,16 @@ impl Satellite {
     /// Returns true if the satellite has an unobstructed line of sight towards
     /// a given point (it is not blocked by the Earth).
     pub fn has_line_of_sight(&self, point: &Vector3<f64>) -> bool {
-        let distance_to_point = self.position.metric_distance(point);
-        let segment_range = 0.0..distance_to_point;
-        let direction = (point - self.position).normalize();
-
-        let d = -direction.dot(&self.position);
-        let nabla = direction.dot(&self.position).powi(2) - self.position.norm_squared() + EARTH_RADIUS.powi(2);
+        has_line_of_sight(&self.position, point)
+    }
 
-        if nabla < 0.0 {
-            true
-        }
-        else if nabla == 0.0 {
-            !segment_range.contains(&d)
-        }
-        else {
-            let nabla = nabla.sqrt();
-            !(segment_range.contains(&(d - nabla)) || segment_range.contains(&(d + nabla)))
-        }
+    /// Maximum distance (in meters) to the points within the view cone.
+    pub fn max_view_distance(&self) -> f64 {
+        self.orbital_plane.semimajor_axis * Self::HALF_ANGLE_DEGREES.to_radians().cos()
     }
 
     pub fn is_in_view_cone(&self, point: &Vector3<f64>) -> bool {
         let half_angle = Self::HALF_ANGLE_DEGREES.to_radians();
-        let max_distance = self.orbital_plane.semimajor_axis * half_angle.cos();
 
         let cone_axis = -self.position.normalize();
         let to_point = point - self.position;
@@ -192,10 +359,11 @@ impl Satellite {
         let distance = to_point.norm();
         let point_angle = to_point.normalize().dot(&cone_axis).acos();
 
-        point_angle <= half_angle && distance <= max_distance
+        point_angle <= half_angle && distance <= self.max_view_distance()
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ConstellationType {
     Delta,
     Star
@@ -208,6 +376,16 @@ impl ConstellationType {
             Self::Star => PI,
         }
     }
+
+    /// Returns true if the first and last orbital planes of the constellation
+    /// are counter-rotating (satellites on either side of the seam move in
+    /// opposite directions).
+    pub fn has_seam(&self) -> bool {
+        match self {
+            Self::Delta => false,
+            Self::Star => true,
+        }
+    }
 }
 
 impl TryFrom<&str> for ConstellationType {
@@ -222,11 +400,15 @@ impl TryFrom<&str> for ConstellationType {
     }
 }
 
+#[derive(Clone)]
 pub struct Model {
     orbital_planes: Vec<Arc<OrbitalPlane>>,
     satellites: Vec<Satellite>,
     t: f64,
+    constellation_type: ConstellationType,
     max_connections: usize,
+    /// Pairs of satellites (smallest id first) that cannot be linked.
+    excluded_links: Arc<HashSet<(usize, usize)>>,
 }
 
 impl Model {
@@ -258,6 +440,7 @@ impl Model {
                     Arc::clone(&orbital_plane),
                     (phase_offset * i as f64 + 2.0 * PI * j as f64 / satellites_per_plane as f64) % 360.0,
                     true,
+                    max_connections,
                 ));
             }
 
@@ -268,7 +451,9 @@ impl Model {
             orbital_planes,
             satellites,
             t: 0.0,
+            constellation_type,
             max_connections,
+            excluded_links: Arc::default(),
         };
 
         model.recalculate_satellite_positions();
@@ -299,19 +484,61 @@ impl Model {
 
     fn recalculate_satellite_positions(&mut self) {
         let t = self.t;
-        for sat in self.satellites_mut() {
-            sat.recalculate_position(t);
-        }
+        self.satellites_mut().par_iter_mut().for_each(|sat| sat.recalculate_position(t));
     }
 
+    pub fn constellation_type(&self) -> ConstellationType {
+        self.constellation_type
+    }
+
+    /// Default number of links that satellites can establish (individual
+    /// satellites may have a different number of terminals).
     pub fn max_connections(&self) -> usize {
         self.max_connections
     }
 
+    /// Returns a copy of the model in which each satellite can only establish
+    /// the given number of links (e.g. to restrict a strategy to a subset of
+    /// the terminals).
+    pub fn with_terminals(&self, terminals: impl Fn(&Satellite) -> usize) -> Model {
+        let mut model = self.clone();
+        for sat in model.satellites_mut() {
+            sat.max_connections = terminals(sat);
+        }
+
+        model
+    }
+
+    /// Returns a copy of the model in which the given pairs of satellites
+    /// cannot be linked (e.g. because another strategy already links them).
+    pub fn without_links(&self, links: impl IntoIterator<Item = (usize, usize)>) -> Model {
+        let mut excluded_links = (*self.excluded_links).clone();
+        excluded_links.extend(links.into_iter().map(|(a, b)| (a.min(b), a.max(b))));
+
+        Model { excluded_links: Arc::new(excluded_links), ..self.clone() }
+    }
+
+    /// Whether a pair of satellites can be linked, provided that they are in
+    /// line of sight and have free terminals.
+    pub fn is_link_allowed(&self, a: usize, b: usize) -> bool {
+        !self.excluded_links.contains(&(a.min(b), a.max(b)))
+    }
+
     pub fn distance_between_satellites(&self, sat1: &Satellite, sat2: &Satellite) -> f64 {
         sat1.position().metric_distance(sat2.position())
     }
 
+    /// Returns the link between two satellites at the current time, whose
+    /// capacity is limited by the slowest of the two terminals.
+    pub fn link(&self, a: usize, b: usize) -> Link {
+        let (sat_a, sat_b) = (&self.satellites[a], &self.satellites[b]);
+
+        Link {
+            length: self.distance_between_satellites(sat_a, sat_b),
+            capacity: sat_a.link_capacity().min(sat_b.link_capacity()),
+        }
+    }
+
     /// Returns the point on the surface of the Earth with the given
     /// latitude and longitude (both in degrees).
     pub fn surface_point(&self, coordinates: &GeoCoordinates) -> Vector3<f64> {
@@ -332,7 +559,16 @@ impl Model {
     }
 }
 
-pub type ConnectionGraph = GraphMap<usize, f64, Undirected>;
+/// Link between two satellites.
+#[derive(Clone, Copy, Debug)]
+pub struct Link {
+    /// Length of the link, in meters.
+    pub length: f64,
+    /// Data rate of the link, in Gbps.
+    pub capacity: f64,
+}
+
+pub type ConnectionGraph = GraphMap<usize, Link, Undirected>;
 
 pub struct Simulation {
     model: Model,
@@ -343,11 +579,29 @@ pub struct Simulation {
     recurrent_failure_probability: f64,
     last_update_timestamp: f64,
     topology: ConnectionGraph,
+    link_churn: (usize, usize),
+    failures_since_update: Vec<usize>,
+    city_pairs: Vec<CityPair>,
+    city_pair_routes: Vec<Option<Route>>,
+    path_histories: Vec<PathHistory>,
+    ground_sites: Vec<GroundSite>,
+    traffic_demands: Vec<TrafficDemand>,
+    region_pairs: Vec<RegionPair>,
+    routing_config: RoutingConfig,
+    /// Routing graph between the locations used by the simulation, updated
+    /// with the topology (and at every step when packets are simulated).
+    routing_graph: RoutingGraph,
+    link_state: Option<LinkStateProtocol>,
+    contact_plan_config: Option<ContactPlanConfig>,
+    contact_routes: Option<Vec<Option<ContactRoute>>>,
+    packet_simulation: Option<PacketSimulation>,
+    packet_statistics: Option<PacketStatistics>,
     strategy: Box<dyn ConnectionStrategy>,
     statistics_channel: Sender<String>,
 }
 
 impl Simulation {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         mut model: Model,
         time_step: f64,
@@ -356,6 +610,14 @@ impl Simulation {
         rng_seed: Option<u64>,
         starting_failure_probability: f64,
         recurrent_failure_probability: f64,
+        city_pairs: Vec<CityPair>,
+        ground_sites: Vec<GroundSite>,
+        traffic_demands: Vec<TrafficDemand>,
+        region_pairs: Vec<RegionPair>,
+        routing_config: RoutingConfig,
+        link_state_config: Option<LinkStateConfig>,
+        contact_plan_config: Option<ContactPlanConfig>,
+        packet_config: Option<PacketConfig>,
         strategy: Box<dyn ConnectionStrategy>,
         statistics_channel: Sender<String>,
     ) -> Self {
@@ -364,14 +626,34 @@ impl Simulation {
             None => StdRng::from_entropy(),
         };
 
+        let mut failures_since_update = Vec::new();
+
         if starting_failure_probability > 0.0 {
             for sat in model.satellites_mut() {
                 if rng.gen::<f64>() < starting_failure_probability {
                     sat.set_status(false);
+                    failures_since_update.push(sat.id());
                 }
             }
         }
 
+        let num_satellites = model.satellites().len();
+        let num_gateways = routing_config.gateways().len();
+        let link_state = link_state_config.map(|config| LinkStateProtocol::new(config, num_satellites, num_gateways));
+        let packet_simulation = packet_config.map(|config| PacketSimulation::new(config, StdRng::seed_from_u64(rng.gen())));
+
+        // Locations between which routes are calculated at every connection update
+        let mut routing_sites: Vec<GeoCoordinates> = Vec::new();
+        let locations = city_pairs.iter().flat_map(|p| [*p.source(), *p.destination()])
+            .chain(ground_sites.iter().map(|s| *s.coordinates()))
+            .chain(packet_simulation.iter().flat_map(|p| p.sites().iter().copied()));
+        for location in locations {
+            if !routing_sites.contains(&location) {
+                routing_sites.push(location);
+            }
+        }
+        let routing_graph = RoutingGraph::new(&model, &GraphMap::new(), &routing_config, &routing_sites);
+
         let mut sim = Simulation {
             model,
             time_step,
@@ -381,6 +663,21 @@ impl Simulation {
             rng,
             recurrent_failure_probability,
             topology: GraphMap::new(),
+            link_churn: (0, 0),
+            failures_since_update,
+            city_pair_routes: Vec::new(),
+            path_histories: city_pairs.iter().map(|_| PathHistory::default()).collect(),
+            city_pairs,
+            ground_sites,
+            traffic_demands,
+            region_pairs,
+            routing_config,
+            routing_graph,
+            link_state,
+            contact_plan_config,
+            contact_routes: None,
+            packet_simulation,
+            packet_statistics: None,
             strategy,
             statistics_channel,
         };
@@ -413,14 +710,55 @@ impl Simulation {
         self.model.orbital_planes()
     }
 
+    pub fn city_pairs(&self) -> &[CityPair] {
+        &self.city_pairs
+    }
+
+    pub fn ground_sites(&self) -> &[GroundSite] {
+        &self.ground_sites
+    }
+
+    pub fn traffic_demands(&self) -> &[TrafficDemand] {
+        &self.traffic_demands
+    }
+
+    pub fn region_pairs(&self) -> &[RegionPair] {
+        &self.region_pairs
+    }
+
+    /// Number of links that were (added, removed) in the most recent
+    /// connection update.
+    pub fn link_churn(&self) -> (usize, usize) {
+        self.link_churn
+    }
+
+    /// Outcome of the packets generated since the previous connection update,
+    /// if packets are simulated.
+    pub fn packet_statistics(&self) -> Option<&PacketStatistics> {
+        self.packet_statistics.as_ref()
+    }
+
     pub fn step(&mut self) {
+        // Forward packets through the current topology until the next time step
+        if let Some(packets) = &mut self.packet_simulation {
+            packets.advance(&self.model, &self.topology, &self.routing_graph, self.model.t() + self.time_step);
+        }
+
         self.model.increment_t(self.time_step);
+        if self.packet_simulation.is_some() {
+            self.routing_graph.update_positions(&self.model);
+        }
+        if let Some(link_state) = &mut self.link_state {
+            link_state.advance(self.model.t());
+        }
+
         if self.t() >= self.last_update_timestamp + self.connection_refresh_interval {
             // Simulate potential satellite failures
             if self.recurrent_failure_probability > 0.0 {
                 for sat in self.model.satellites_mut() {
                     if sat.status() && self.rng.gen::<f64>() < self.recurrent_failure_probability {
                         sat.set_status(false);
+                        self.failures_since_update.push(sat.id());
                     }
                 }
             }
@@ -430,61 +768,252 @@ impl Simulation {
     }
 
     pub fn update_connections(&mut self) {
+        // Satellites whose links changed, which notify the routing protocol. The
+        // neighbors of satellites that failed during the last interval are taken
+        // before these are removed below, while satellites failed through
+        // `simulate_failure` are already removed and their neighbors notified.
+        let mut changed: Vec<usize> = self.failures_since_update.iter()
+            .filter(|&&id| self.topology.contains_node(id))
+            .flat_map(|&id| self.topology.neighbors(id).collect::<Vec<_>>())
+            .collect();
+
+        // Links of failed satellites are not part of the previous topology
+        let edge_count = self.topology.edge_count();
+        for &id in &self.failures_since_update {
+            self.topology.remove_node(id);
+        }
+        let removed_by_failures = edge_count - self.topology.edge_count();
+
+        let context = StrategyContext {
+            previous: &self.topology,
+            t: self.t(),
+            elapsed: self.t() - self.last_update_timestamp,
+            failures: &self.failures_since_update,
+        };
+
+        let topology = match self.strategy.update(&self.model, &context) {
+            TopologyUpdate::Full(topology) => topology,
+            TopologyUpdate::Diff(diff) => diff.apply(&self.topology, &self.model),
+        };
+
         self.last_update_timestamp = self.t();
-        self.topology = self.strategy.run(&self.model);
+        self.failures_since_update.clear();
+
+        let added = topology.all_edges().filter(|(a, b, _)| !self.topology.contains_edge(*a, *b)).count();
+        let removed = self.topology.all_edges().filter(|(a, b, _)| !topology.contains_edge(*a, *b)).count();
+        self.link_churn = (added, removed + removed_by_failures);
+
+        if self.link_state.is_some() {
+            changed.extend(topology.all_edges()
+                .chain(self.topology.all_edges())
+                .filter(|(a, b, _)| topology.contains_edge(*a, *b) != self.topology.contains_edge(*a, *b))
+                .flat_map(|(a, b, _)| [a, b]));
+        }
+
+        self.topology = topology;
+        self.routing_graph.update_topology(&self.model, &self.topology);
+        self.update_city_pair_routes();
+        self.notify_link_state(&changed);
+        self.update_contact_routes();
+        self.packet_statistics = self.packet_simulation.as_mut().map(PacketSimulation::take_statistics);
 
         // Send statistics message
         self.statistics_channel.send(statistics_msg(self)).unwrap();
     }
 
     /// Calculates round trip time (RTT) in seconds between two locations
-    /// specified using geographical coordinates. Expensive calculation since it
-    /// requires pathfinding algorithms and cloning the topology.
+    /// specified using geographical coordinates. Routes between the locations
+    /// used by the simulation (city pairs, ground sites and packet flows) reuse
+    /// the shortest paths calculated since the last update; other locations
+    /// require building a routing graph.
     pub fn calc_rtt(&self, c1: &GeoCoordinates, c2: &GeoCoordinates) -> Option<f64> {
-        let mut topology = self.topology.clone();
-        let satellites = self.satellites();
-
-        // Update edge weights (distances between satellites) according to most recent timestamp
-        for edge in topology.all_edges_mut() {
-            let pos1 = satellites[edge.0].position();
-            let pos2 = satellites[edge.1].position();
-            *edge.2 = pos1.metric_distance(pos2);
+        self.route(c1, c2).map(|route| route.rtt())
+    }
+
+    /// Read-only view of the current network, which can be shared between
+    /// threads (unlike the simulation, whose strategy is only `Send`).
+    pub fn network(&self) -> NetworkSnapshot<'_> {
+        NetworkSnapshot {
+            model: &self.model,
+            topology: &self.topology,
+            routing_config: &self.routing_config,
+            routing_graph: &self.routing_graph,
         }
+    }
 
-        let nodes: Vec<usize> = topology.nodes().collect();
+    /// Finds the shortest route between two locations, which may be relayed
+    /// through the configured gateways.
+    pub fn route(&self, c1: &GeoCoordinates, c2: &GeoCoordinates) -> Option<Route> {
+        self.network().route(c1, c2)
+    }
 
-        let p1 = self.model.surface_point(c1);
-        let p2 = self.model.surface_point(c2);
+    /// Finds up to `k` shortest loopless routes between two locations, in
+    /// increasing order of length.
+    pub fn k_shortest_routes(&self, c1: &GeoCoordinates, c2: &GeoCoordinates, k: usize) -> Vec<Route> {
+        self.network().k_shortest_routes(c1, c2, k)
+    }
 
-        let id1 = satellites.len();
-        let id2 = id1 + 1;
+    /// Finds a maximal set of link or node-disjoint routes between two
+    /// locations, starting with the shortest route.
+    pub fn disjoint_routes(&self, c1: &GeoCoordinates, c2: &GeoCoordinates, disjointness: Disjointness) -> Vec<Route> {
+        self.network().disjoint_routes(c1, c2, disjointness)
+    }
 
-        // Add links between surface points and satellites when there is visibility between them
-        for sat in nodes.iter().map(|id| &satellites[*id]) {
-            if sat.is_in_view_cone(&p1) {
-                topology.add_edge(id1, sat.id(), p1.metric_distance(sat.position()));
-            }
+    /// Routes the traffic demands through the current topology.
+    pub fn flow_assignment(&self) -> FlowAssignment {
+        FlowAssignment::new(&self.model, &self.topology, &self.routing_config, &self.traffic_demands)
+    }
 
-            if sat.is_in_view_cone(&p2) {
-                topology.add_edge(sat.id(), id2, sat.position().metric_distance(&p2));
-            }
-        }
+    /// Calculates the maximum traffic (in Gbps) that the current topology can
+    /// carry from a set of ground stations to another.
+    pub fn max_flow(&self, sources: &[GeoCoordinates], destinations: &[GeoCoordinates]) -> f64 {
+        self.network().max_flow(sources, destinations)
+    }
 
-        astar(
-            &topology,
-            id1,
-            |n| n == id2,
-            |e| *e.weight(),
-            |n| match n {
-                _ if n == id1 => p1.metric_distance(&p2),
-                _ if n == id2 => 0.0,
-                _ => satellites[n].position().metric_distance(&p2)
-            }
-        ).map(|(cost, _)| 2.0 * cost / LIGHT_SPEED)
+    /// Calculates the round trip time (in seconds) between every pair of
+    /// ground sites.
+    pub fn rtt_matrix(&self) -> Vec<Vec<Option<f64>>> {
+        let sites: Vec<usize> = self.ground_sites.iter()
+            .map(|s| self.routing_graph.site_index(s.coordinates()).unwrap())
+            .collect();
+        self.network().with_routing_graph(|graph| graph.rtt_matrix(&sites))
     }
 
     pub fn simulate_failure(&mut self, id: usize) {
+        let neighbors: Vec<usize> = self.topology.neighbors(id).collect();
+
         self.model.satellites_mut()[id].set_status(false);
         self.topology.remove_node(id);
+        self.routing_graph.update_topology(&self.model, &self.topology);
+        self.failures_since_update.push(id);
+
+        self.notify_link_state(&neighbors);
+    }
+
+    /// Informs the routing protocol (if emulated) that the links of the given
+    /// satellites changed.
+    fn notify_link_state(&mut self, changed: &[usize]) {
+        if let Some(link_state) = &mut self.link_state {
+            let alive = self.model.satellites().iter().map(Satellite::status).collect();
+            link_state.topology_changed(&self.routing_graph, alive, changed, self.model.t());
+        }
+    }
+
+    /// Finds the routes between city pairs in the current topology, keeping
+    /// track of route changes.
+    fn update_city_pair_routes(&mut self) {
+        let network = self.network();
+        self.city_pair_routes = self.city_pairs.par_iter().map(|p| network.route(p.source(), p.destination())).collect();
+
+        let t = self.t();
+        for (history, route) in self.path_histories.iter_mut().zip(&self.city_pair_routes) {
+            history.update(route.as_ref(), t);
+        }
+    }
+
+    /// Shortest route between each city pair at the most recent connection
+    /// update.
+    pub fn city_pair_routes(&self) -> &[Option<Route>] {
+        &self.city_pair_routes
+    }
+
+    pub fn path_histories(&self) -> &[PathHistory] {
+        &self.path_histories
+    }
+
+    /// Predicts the contact plan between a set of ground sites, starting from
+    /// the current topology, if contact plans are configured.
+    pub fn predict_contact_plan(&self, sites: &[GeoCoordinates]) -> Option<ContactPlan> {
+        self.contact_plan_config.as_ref().map(|config| ContactPlan::predict(
+            &self.model, &self.topology, &self.routing_config, sites, (config.new_strategy)().as_mut(), config.horizon, config.interval,
+        ))
+    }
+
+    /// Finds the earliest-arrival routes between city pairs through the
+    /// predicted contact plan.
+    fn update_contact_routes(&mut self) {
+        let sites: Vec<GeoCoordinates> = self.city_pairs.iter().flat_map(|p| [*p.source(), *p.destination()]).collect();
+        let t = self.t();
+
+        self.contact_routes = self.predict_contact_plan(&sites).map(|plan| {
+            (0..sites.len() / 2).map(|i| plan.earliest_arrival(2 * i, 2 * i + 1, t)).collect()
+        });
+    }
+
+    /// Earliest-arrival route between each city pair through the contact
+    /// plan predicted at the most recent connection update, if contact plans
+    /// are configured.
+    pub fn contact_routes(&self) -> Option<&[Option<ContactRoute>]> {
+        self.contact_routes.as_deref()
+    }
+
+    /// State of the emulated routing protocol, if any.
+    pub fn link_state(&self) -> Option<&LinkStateProtocol> {
+        self.link_state.as_ref()
+    }
+}
+
+/// Read-only view of the network of a simulation at a point in time: the
+/// satellites, the topology and the routing graph between the locations used
+/// by the simulation.
+#[derive(Clone, Copy)]
+pub struct NetworkSnapshot<'a> {
+    model: &'a Model,
+    topology: &'a ConnectionGraph,
+    routing_config: &'a RoutingConfig,
+    routing_graph: &'a RoutingGraph,
+}
+
+impl NetworkSnapshot<'_> {
+    /// Indices of two distinct locations in the routing graph of the
+    /// simulation, if it contains both.
+    fn routing_sites(&self, c1: &GeoCoordinates, c2: &GeoCoordinates) -> Option<(usize, usize)> {
+        let from = self.routing_graph.site_index(c1)?;
+        let to = self.routing_graph.site_index(c2)?;
+
+        Some((from, to)).filter(|_| from != to)
+    }
+
+    /// Runs a query on the routing graph of the simulation, which is rebuilt
+    /// if the satellites moved since it was last updated.
+    fn with_routing_graph<T>(&self, query: impl FnOnce(&RoutingGraph) -> T) -> T {
+        if self.routing_graph.t() == self.model.t() {
+            query(self.routing_graph)
+        } else {
+            query(&RoutingGraph::new(self.model, self.topology, self.routing_config, self.routing_graph.sites()))
+        }
+    }
+
+    /// Finds the shortest route between two locations, which may be relayed
+    /// through the configured gateways.
+    pub fn route(&self, c1: &GeoCoordinates, c2: &GeoCoordinates) -> Option<Route> {
+        match self.routing_sites(c1, c2) {
+            Some((from, to)) => self.with_routing_graph(|graph| graph.route(from, to)),
+            None => routing::shortest_route(self.model, self.topology, self.routing_config, c1, c2),
+        }
+    }
+
+    /// Finds up to `k` shortest loopless routes between two locations, in
+    /// increasing order of length.
+    pub fn k_shortest_routes(&self, c1: &GeoCoordinates, c2: &GeoCoordinates, k: usize) -> Vec<Route> {
+        match self.routing_sites(c1, c2) {
+            Some((from, to)) => self.with_routing_graph(|graph| graph.k_shortest_routes(from, to, k)),
+            None => RoutingGraph::new(self.model, self.topology, self.routing_config, &[*c1, *c2]).k_shortest_routes(0, 1, k),
+        }
+    }
+
+    /// Finds a maximal set of link or node-disjoint routes between two
+    /// locations, starting with the shortest route.
+    pub fn disjoint_routes(&self, c1: &GeoCoordinates, c2: &GeoCoordinates, disjointness: Disjointness) -> Vec<Route> {
+        match self.routing_sites(c1, c2) {
+            Some((from, to)) => self.with_routing_graph(|graph| graph.disjoint_routes(from, to, disjointness)),
+            None => RoutingGraph::new(self.model, self.topology, self.routing_config, &[*c1, *c2]).disjoint_routes(0, 1, disjointness),
+        }
+    }
+
+    /// Calculates the maximum traffic (in Gbps) that the current topology can
+    /// carry from a set of ground stations to another.
+    pub fn max_flow(&self, sources: &[GeoCoordinates], destinations: &[GeoCoordinates]) -> f64 {
+        flow_model::max_flow(self.model, self.topology, sources, destinations)
     }
 }
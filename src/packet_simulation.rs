@@ -0,0 +1,370 @@
+use std::{cmp::Ordering, collections::{BinaryHeap, HashMap, VecDeque}};
+
+use rand::{Rng, rngs::StdRng};
+
+use crate::{model::{ConnectionGraph, GeoCoordinates, Model, LIGHT_SPEED}, routing::RoutingGraph};
+
+/// Stream of packets sent from one location to another, with exponentially
+/// distributed inter-arrival times.
+#[derive(Clone)]
+pub struct PacketFlow {
+    source: GeoCoordinates,
+    destination: GeoCoordinates,
+    /// Average number of packets sent per second.
+    rate: f64,
+}
+
+impl PacketFlow {
+    pub fn new(source: GeoCoordinates, destination: GeoCoordinates, rate: f64) -> Self {
+        assert!(rate > 0.0);
+
+        PacketFlow { source, destination, rate }
+    }
+
+    pub fn source(&self) -> &GeoCoordinates {
+        &self.source
+    }
+
+    pub fn destination(&self) -> &GeoCoordinates {
+        &self.destination
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+}
+
+#[derive(Clone)]
+pub struct PacketConfig {
+    flows: Vec<PacketFlow>,
+    /// Size of every packet, in bytes.
+    packet_size: f64,
+    /// Maximum number of packets waiting to be transmitted on each link.
+    queue_size: usize,
+}
+
+impl PacketConfig {
+    pub fn new(flows: Vec<PacketFlow>, packet_size: f64, queue_size: usize) -> Self {
+        assert!(packet_size > 0.0);
+
+        PacketConfig { flows, packet_size, queue_size }
+    }
+
+    pub fn flows(&self) -> &[PacketFlow] {
+        &self.flows
+    }
+
+    /// Time (in seconds) a link takes to transmit a packet. Links between
+    /// satellites transmit at the capacity of the link in the topology and
+    /// links with the ground at the capacity of the satellite's terminals,
+    /// while the terrestrial network between gateways is not a bottleneck.
+    fn transmission_delay(&self, model: &Model, topology: &ConnectionGraph, from: usize, to: usize) -> f64 {
+        let num_satellites = model.satellites().len();
+        let capacity = match (from < num_satellites, to < num_satellites) {
+            (true, true) => topology.edge_weight(from, to).unwrap().capacity,
+            (true, false) => model.satellites()[from].link_capacity(),
+            (false, true) => model.satellites()[to].link_capacity(),
+            (false, false) => return 0.0,
+        };
+
+        self.packet_size * 8.0 / (capacity * 1e9)
+    }
+}
+
+/// Outcome of the packets generated since the statistics were last taken.
+#[derive(Clone, Default)]
+pub struct PacketStatistics {
+    pub generated: usize,
+    pub delivered: usize,
+    /// Packets dropped because the queue of a link was full.
+    pub dropped_queue: usize,
+    /// Packets dropped because their destination was unreachable.
+    pub dropped_no_route: usize,
+    /// Packets dropped because the link they were waiting for was removed.
+    pub dropped_link_down: usize,
+    /// End-to-end delays (in seconds) of delivered packets.
+    pub delays: Vec<f64>,
+}
+
+impl PacketStatistics {
+    pub fn dropped(&self) -> usize {
+        self.dropped_queue + self.dropped_no_route + self.dropped_link_down
+    }
+
+    pub fn mean_delay(&self) -> Option<f64> {
+        match self.delays.len() {
+            0 => None,
+            n => Some(self.delays.iter().sum::<f64>() / n as f64),
+        }
+    }
+
+    /// Nearest-rank percentile (`p` in [0, 100]) of end-to-end delays.
+    pub fn delay_percentile(&self, p: f64) -> Option<f64> {
+        let mut delays = self.delays.clone();
+        delays.sort_unstable_by(f64::total_cmp);
+
+        let rank = ((p / 100.0 * delays.len() as f64).ceil() as usize).max(1);
+        delays.get(rank - 1).copied()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Packet {
+    id: u64,
+    flow: usize,
+    created: f64,
+}
+
+enum EventKind {
+    /// A flow sends a new packet.
+    Generation { flow: usize },
+    /// A packet reaches a node (after propagating through a link).
+    Arrival { node: usize, packet: Packet },
+    /// A link finishes transmitting a packet.
+    TransmissionEnd { from: usize, to: usize, packet_id: u64 },
+}
+
+/// Scheduled event, ordered so that the earliest event (and, between
+/// simultaneous events, the first one scheduled) is popped first.
+struct Event {
+    time: f64,
+    sequence: u64,
+    kind: EventKind,
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Event {}
+
+impl PartialOrd for Event {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Event {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.time.total_cmp(&self.time).then(other.sequence.cmp(&self.sequence))
+    }
+}
+
+/// Output of a directed link, which transmits one packet at a time and holds
+/// the remaining ones in a finite queue.
+struct Transmitter {
+    propagation_delay: f64,
+    transmission_delay: f64,
+    transmitting: Option<Packet>,
+    queue: VecDeque<Packet>,
+}
+
+/// Discrete-event simulation of packets forwarded through the topology.
+/// Nodes (identified as in the `RoutingGraph`) forward packets according to
+/// routing tables that are recalculated whenever the simulation advances, so
+/// that packets follow the links of the most recent topology.
+pub struct PacketSimulation {
+    config: PacketConfig,
+    rng: StdRng,
+    /// Sources and destinations of the flows (in this order, for each flow).
+    sites: Vec<GeoCoordinates>,
+    /// Nodes of the source and destination of each flow in the routing graph.
+    flow_nodes: Vec<(usize, usize)>,
+    /// Next hops towards the destination of each flow.
+    routing_tables: Vec<Vec<Option<usize>>>,
+    transmitters: HashMap<(usize, usize), Transmitter>,
+    events: BinaryHeap<Event>,
+    next_sequence: u64,
+    next_packet_id: u64,
+    statistics: PacketStatistics,
+}
+
+impl PacketSimulation {
+    pub fn new(config: PacketConfig, rng: StdRng) -> Self {
+        let sites = config.flows.iter().flat_map(|f| [f.source, f.destination]).collect();
+
+        let mut simulation = PacketSimulation {
+            config,
+            rng,
+            sites,
+            flow_nodes: Vec::new(),
+            routing_tables: Vec::new(),
+            transmitters: HashMap::new(),
+            events: BinaryHeap::new(),
+            next_sequence: 0,
+            next_packet_id: 0,
+            statistics: PacketStatistics::default(),
+        };
+
+        for flow in 0..simulation.config.flows.len() {
+            let delay = simulation.interarrival_time(flow);
+            simulation.schedule(delay, EventKind::Generation { flow });
+        }
+
+        simulation
+    }
+
+    /// Ground sites the routing graph passed to `advance` must include.
+    pub fn sites(&self) -> &[GeoCoordinates] {
+        &self.sites
+    }
+
+    /// Returns the statistics collected since the last call, resetting them.
+    pub fn take_statistics(&mut self) -> PacketStatistics {
+        std::mem::take(&mut self.statistics)
+    }
+
+    /// Processes every event up to a given time, forwarding packets through
+    /// the links of a routing graph built from the given topology. Packets
+    /// waiting for links that no longer exist are dropped.
+    pub fn advance(&mut self, model: &Model, topology: &ConnectionGraph, graph: &RoutingGraph, until: f64) {
+        let flow_sites: Vec<(usize, usize)> = self.sites.chunks(2)
+            .map(|s| (graph.site_index(&s[0]).unwrap(), graph.site_index(&s[1]).unwrap()))
+            .collect();
+        self.flow_nodes = flow_sites.iter().map(|&(source, destination)| (graph.site_node(source), graph.site_node(destination))).collect();
+        self.routing_tables = flow_sites.iter().map(|&(_, destination)| graph.next_hops(destination)).collect();
+
+        let mut dropped = 0;
+        let config = &self.config;
+        self.transmitters.retain(|&(from, to), transmitter| match graph.link_length(from, to) {
+            Some(length) => {
+                transmitter.propagation_delay = length / LIGHT_SPEED;
+                transmitter.transmission_delay = config.transmission_delay(model, topology, from, to);
+                true
+            },
+            None => {
+                dropped += transmitter.queue.len() + transmitter.transmitting.iter().count();
+                false
+            },
+        });
+        self.statistics.dropped_link_down += dropped;
+
+        while self.events.peek().is_some_and(|e| e.time <= until) {
+            let event = self.events.pop().unwrap();
+
+            match event.kind {
+                EventKind::Generation { flow } => {
+                    let packet = Packet { id: self.next_packet_id, flow, created: event.time };
+                    self.next_packet_id += 1;
+                    self.statistics.generated += 1;
+
+                    self.forward(model, topology, graph, self.flow_nodes[flow].0, packet, event.time);
+
+                    let delay = self.interarrival_time(flow);
+                    self.schedule(event.time + delay, EventKind::Generation { flow });
+                },
+                EventKind::Arrival { node, packet } => {
+                    if node == self.flow_nodes[packet.flow].1 {
+                        self.statistics.delivered += 1;
+                        self.statistics.delays.push(event.time - packet.created);
+                    } else {
+                        self.forward(model, topology, graph, node, packet, event.time);
+                    }
+                },
+                EventKind::TransmissionEnd { from, to, packet_id } => {
+                    // The link may have been removed (and its packets dropped) during the transmission
+                    let transmitter = match self.transmitters.get_mut(&(from, to)) {
+                        Some(t) if t.transmitting.is_some_and(|p| p.id == packet_id) => t,
+                        _ => continue,
+                    };
+
+                    let packet = transmitter.transmitting.take().unwrap();
+                    let arrival = event.time + transmitter.propagation_delay;
+                    let end = event.time + transmitter.transmission_delay;
+                    let next = transmitter.queue.pop_front();
+                    transmitter.transmitting = next;
+
+                    self.schedule(arrival, EventKind::Arrival { node: to, packet });
+                    if let Some(next) = next {
+                        self.schedule(end, EventKind::TransmissionEnd { from, to, packet_id: next.id });
+                    }
+                },
+            }
+        }
+    }
+
+    /// Sends a packet from a node to the next hop towards its destination.
+    fn forward(&mut self, model: &Model, topology: &ConnectionGraph, graph: &RoutingGraph, node: usize, packet: Packet, t: f64) {
+        let next_hop = match self.routing_tables[packet.flow][node] {
+            Some(next_hop) => next_hop,
+            None => {
+                self.statistics.dropped_no_route += 1;
+                return;
+            },
+        };
+
+        let transmitter = self.transmitters.entry((node, next_hop)).or_insert_with(|| Transmitter {
+            propagation_delay: graph.link_length(node, next_hop).unwrap() / LIGHT_SPEED,
+            transmission_delay: self.config.transmission_delay(model, topology, node, next_hop),
+            transmitting: None,
+            queue: VecDeque::new(),
+        });
+
+        if transmitter.transmitting.is_none() {
+            transmitter.transmitting = Some(packet);
+            let end = t + transmitter.transmission_delay;
+            self.schedule(end, EventKind::TransmissionEnd { from: node, to: next_hop, packet_id: packet.id });
+        } else if transmitter.queue.len() < self.config.queue_size {
+            transmitter.queue.push_back(packet);
+        } else {
+            self.statistics.dropped_queue += 1;
+        }
+    }
+
+    fn interarrival_time(&mut self, flow: usize) -> f64 {
+        -(1.0 - self.rng.gen::<f64>()).ln() / self.config.flows[flow].rate
+    }
+
+    fn schedule(&mut self, time: f64, kind: EventKind) {
+        self.events.push(Event { time, sequence: self.next_sequence, kind });
+        self.next_sequence += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::{connection_strategy::{ConnectionStrategy, GridStrategy}, model::{ConstellationType, EARTH_RADIUS}, routing::RoutingConfig};
+
+    /// Packets that have been generated but neither delivered nor dropped.
+    fn in_flight(simulation: &PacketSimulation) -> usize {
+        let transmitted = simulation.transmitters.values().map(|t| t.queue.len() + t.transmitting.iter().count()).sum::<usize>();
+        let propagating = simulation.events.iter().filter(|e| matches!(e.kind, EventKind::Arrival { .. })).count();
+
+        transmitted + propagating
+    }
+
+    #[test]
+    fn packets_are_conserved() {
+        let model = Model::new(12, 24, 53f64.to_radians(), ConstellationType::Delta, 1, EARTH_RADIUS + 0.55e6, 4);
+        let mut topology = GridStrategy::new(1, None, true).run(&model);
+        let (london, paris, new_york) = (GeoCoordinates::new(51.5, -0.1), GeoCoordinates::new(48.9, 2.4), GeoCoordinates::new(40.7, -74.0));
+
+        // Enough traffic to fill the queues of the uplinks
+        let flows = vec![PacketFlow::new(london, new_york, 1e6), PacketFlow::new(paris, new_york, 1e6)];
+        let mut simulation = PacketSimulation::new(PacketConfig::new(flows, 1500.0, 10), StdRng::seed_from_u64(0));
+        let config = RoutingConfig::default();
+
+        let graph = RoutingGraph::new(&model, &topology, &config, simulation.sites());
+        simulation.advance(&model, &topology, &graph, 0.01);
+
+        // Remove a link between satellites while it transmits a packet, which is dropped
+        let num_satellites = model.satellites().len();
+        let &(a, b) = simulation.transmitters.iter()
+            .find(|&(&(a, b), t)| a < num_satellites && b < num_satellites && t.transmitting.is_some())
+            .unwrap().0;
+        topology.remove_edge(a, b);
+
+        let graph = RoutingGraph::new(&model, &topology, &config, simulation.sites());
+        simulation.advance(&model, &topology, &graph, 0.05);
+
+        let pending = in_flight(&simulation);
+        let statistics = simulation.take_statistics();
+        assert!(statistics.delivered > 0 && statistics.dropped_queue > 0 && statistics.dropped_link_down > 0);
+        assert_eq!(statistics.generated, statistics.delivered + statistics.dropped() + pending);
+    }
+}
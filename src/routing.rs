@@ -0,0 +1,837 @@
+use std::{cmp::Ordering, collections::{BinaryHeap, HashMap, HashSet}, sync::{Arc, Mutex}};
+
+use nalgebra::Vector3;
+use rayon::prelude::*;
+
+use crate::model::{Model, ConnectionGraph, GeoCoordinates, LIGHT_SPEED};
+
+/// Ground station that can relay traffic between satellites in view, either
+/// by bouncing it back to another satellite (bent-pipe) or by forwarding it
+/// to another gateway through the terrestrial network.
+#[derive(Clone)]
+pub struct Gateway {
+    name: String,
+    coordinates: GeoCoordinates,
+}
+
+impl Gateway {
+    pub fn new(name: &str, coordinates: GeoCoordinates) -> Self {
+        Gateway { name: name.to_owned(), coordinates }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn coordinates(&self) -> &GeoCoordinates {
+        &self.coordinates
+    }
+}
+
+/// How traffic between two locations is spread over multiple routes.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Multipath {
+    /// All traffic follows the shortest route.
+    #[default]
+    ShortestPath,
+    /// Equal-cost multipath: every node splits traffic evenly between the
+    /// next hops on routes whose total length is within the given tolerance
+    /// (as a fraction of the shortest length) of the shortest route.
+    Ecmp(f64),
+    /// Traffic is split between the given number of shortest routes,
+    /// inversely proportionally to their length.
+    Weighted(usize),
+}
+
+/// Allowance (in meters) for rounding errors when comparing the length of a
+/// route with that of the shortest route.
+const LENGTH_SLACK: f64 = 1e-3;
+
+/// Fractions of traffic below this value are treated as zero.
+const FRACTION_EPSILON: f64 = 1e-9;
+
+/// Ground infrastructure available to routing, in addition to the links
+/// between satellites, and how traffic is spread over multiple routes.
+#[derive(Clone, Default)]
+pub struct RoutingConfig {
+    gateways: Vec<Gateway>,
+    /// One-way latency (in seconds) of the terrestrial backhaul between any
+    /// two gateways.
+    backhaul_latency: f64,
+    multipath: Multipath,
+}
+
+impl RoutingConfig {
+    pub fn new(gateways: Vec<Gateway>, backhaul_latency: f64) -> Self {
+        assert!(backhaul_latency >= 0.0);
+
+        RoutingConfig { gateways, backhaul_latency, multipath: Multipath::ShortestPath }
+    }
+
+    pub fn with_multipath(mut self, multipath: Multipath) -> Self {
+        self.multipath = multipath;
+        self
+    }
+
+    pub fn multipath(&self) -> Multipath {
+        self.multipath
+    }
+
+    pub fn gateways(&self) -> &[Gateway] {
+        &self.gateways
+    }
+
+    pub fn backhaul_latency(&self) -> f64 {
+        self.backhaul_latency
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RouteNode {
+    Source,
+    Destination,
+    Satellite(usize),
+    /// Gateway, identified by its index in the routing configuration.
+    Gateway(usize),
+}
+
+/// Link traversed by a route.
+pub struct Hop {
+    pub from: RouteNode,
+    pub to: RouteNode,
+    /// Length of the link, in meters (for terrestrial backhaul links, the
+    /// great-circle distance between the gateways).
+    pub distance: f64,
+    /// One-way propagation delay of the link, in seconds.
+    pub delay: f64,
+}
+
+/// Path found between two locations.
+pub struct Route {
+    length: f64,
+    nodes: Vec<RouteNode>,
+    hops: Vec<Hop>,
+}
+
+impl Route {
+    /// Length of the route in meters (terrestrial backhaul links count as the
+    /// distance light travels during their latency).
+    pub fn length(&self) -> f64 {
+        self.length
+    }
+
+    /// Sequence of nodes traversed by the route, from the source to the
+    /// destination.
+    pub fn nodes(&self) -> &[RouteNode] {
+        &self.nodes
+    }
+
+    pub fn hops(&self) -> &[Hop] {
+        &self.hops
+    }
+
+    pub fn hop_count(&self) -> usize {
+        self.hops.len()
+    }
+
+    /// Ids of the satellites traversed by the route, in order.
+    pub fn satellites(&self) -> impl Iterator<Item = usize> + '_ {
+        self.nodes.iter().filter_map(|n| match n {
+            RouteNode::Satellite(id) => Some(*id),
+            _ => None,
+        })
+    }
+
+    /// Links between satellites traversed by the route, in order.
+    pub fn satellite_links(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.hops.iter().filter_map(|h| match (h.from, h.to) {
+            (RouteNode::Satellite(a), RouteNode::Satellite(b)) => Some((a, b)),
+            _ => None,
+        })
+    }
+
+    /// Returns true if the route is relayed through at least one gateway.
+    pub fn uses_ground_relay(&self) -> bool {
+        self.nodes.iter().any(|n| matches!(n, RouteNode::Gateway(_)))
+    }
+
+    /// One-way propagation delay of the route, in seconds.
+    pub fn delay(&self) -> f64 {
+        self.hops.iter().map(|h| h.delay).sum()
+    }
+
+    /// Round trip time of the route, in seconds.
+    pub fn rtt(&self) -> f64 {
+        2.0 * self.delay()
+    }
+}
+
+/// Entry of the priority queue used by shortest path searches, ordered so
+/// that the entry with the lowest estimated cost is popped first.
+pub(crate) struct SearchState {
+    pub estimate: f64,
+    pub node: usize,
+}
+
+impl PartialEq for SearchState {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for SearchState {}
+
+impl PartialOrd for SearchState {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SearchState {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+/// Distances (in meters) and predecessors of every node in the routing graph,
+/// relative to a source node.
+struct ShortestPaths {
+    distances: Vec<f64>,
+    predecessors: Vec<Option<usize>>,
+}
+
+/// Nodes and links that shortest path searches must avoid.
+#[derive(Default)]
+struct Exclusions {
+    nodes: HashSet<usize>,
+    links: HashSet<(usize, usize)>,
+}
+
+impl Exclusions {
+    fn exclude_link(&mut self, a: usize, b: usize) {
+        self.links.insert((a.min(b), a.max(b)));
+    }
+
+    fn excludes_link(&self, a: usize, b: usize) -> bool {
+        self.links.contains(&(a.min(b), a.max(b)))
+    }
+}
+
+/// Resource that routes returned by disjoint path queries must not share.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Disjointness {
+    /// Routes share no links (but may traverse the same satellites).
+    Link,
+    /// Routes share no satellites or gateways.
+    Node,
+}
+
+/// Topology augmented with ground nodes (gateways and a set of ground sites
+/// between which routes are calculated), with link lengths corresponding to
+/// the satellite positions. The graph can be kept across time steps and
+/// updated in place, and the shortest path trees calculated by its queries
+/// are reused until the next update.
+///
+/// Nodes are identified by satellite ids, followed by the gateways and then
+/// by the ground sites.
+pub struct RoutingGraph {
+    config: RoutingConfig,
+    num_satellites: usize,
+    /// Coordinates of the gateways, followed by those of the ground sites.
+    ground: Vec<GeoCoordinates>,
+    /// Whether each satellite is part of the topology.
+    in_topology: Vec<bool>,
+    adjacency: Vec<Vec<(usize, f64)>>,
+    positions: Vec<Vector3<f64>>,
+    /// Time of the satellite positions the graph corresponds to.
+    t: f64,
+    /// Shortest path trees calculated since the last update, by source node.
+    trees: Mutex<HashMap<usize, Arc<ShortestPaths>>>,
+}
+
+impl RoutingGraph {
+    pub fn new(model: &Model, topology: &ConnectionGraph, config: &RoutingConfig, sites: &[GeoCoordinates]) -> Self {
+        let num_satellites = model.satellites().len();
+        let ground: Vec<GeoCoordinates> = config.gateways.iter().map(|g| g.coordinates).chain(sites.iter().copied()).collect();
+
+        let mut graph = RoutingGraph {
+            config: config.clone(),
+            num_satellites,
+            in_topology: vec![false; num_satellites],
+            adjacency: vec![Vec::new(); num_satellites + ground.len()],
+            positions: Vec::with_capacity(num_satellites + ground.len()),
+            t: model.t(),
+            ground,
+            trees: Mutex::default(),
+        };
+        graph.update_topology(model, topology);
+
+        graph
+    }
+
+    /// Replaces the links between satellites with those of a new topology.
+    pub fn update_topology(&mut self, model: &Model, topology: &ConnectionGraph) {
+        for links in &mut self.adjacency {
+            links.clear();
+        }
+
+        self.in_topology.fill(false);
+        for id in topology.nodes() {
+            self.in_topology[id] = true;
+        }
+
+        // Lengths are set once the positions are updated
+        for (a, b, _) in topology.all_edges() {
+            self.add_edge(a, b, 0.0);
+        }
+
+        // Gateways are fully connected through the terrestrial network
+        let backhaul_length = self.config.backhaul_latency * LIGHT_SPEED;
+        let num_gateways = self.config.gateways.len();
+        for i in 0..num_gateways {
+            for j in i + 1..num_gateways {
+                self.add_edge(self.num_satellites + i, self.num_satellites + j, backhaul_length);
+            }
+        }
+
+        self.update_positions(model);
+    }
+
+    /// Recalculates the lengths of the links according to the current time of
+    /// the model, along with the links between ground nodes and satellites,
+    /// which exist when there is visibility between them.
+    pub fn update_positions(&mut self, model: &Model) {
+        let satellites = model.satellites();
+        let num_satellites = self.num_satellites;
+
+        self.t = model.t();
+        self.positions.clear();
+        self.positions.extend(satellites.iter().map(|s| *s.position()));
+        self.positions.extend(self.ground.iter().map(|c| model.surface_point(c)));
+
+        // Ground links are added again below, while backhaul links keep their length
+        for (a, links) in self.adjacency.iter_mut().enumerate() {
+            if a < num_satellites {
+                links.retain(|&(b, _)| b < num_satellites);
+                for (b, length) in links.iter_mut() {
+                    *length = self.positions[a].metric_distance(&self.positions[*b]);
+                }
+            } else {
+                links.retain(|&(b, _)| b >= num_satellites);
+            }
+        }
+
+        for ground in num_satellites..self.positions.len() {
+            let point = self.positions[ground];
+            for sat in satellites.iter().filter(|s| self.in_topology[s.id()]) {
+                // Most satellites are discarded by comparing their distance before the view cone test
+                let max_distance = sat.max_view_distance();
+                if (point - sat.position()).norm_squared() <= max_distance * max_distance && sat.is_in_view_cone(&point) {
+                    let length = point.metric_distance(sat.position());
+                    self.adjacency[ground].push((sat.id(), length));
+                    self.adjacency[sat.id()].push((ground, length));
+                }
+            }
+        }
+
+        self.trees.get_mut().unwrap().clear();
+    }
+
+    fn add_edge(&mut self, a: usize, b: usize, length: f64) {
+        self.adjacency[a].push((b, length));
+        self.adjacency[b].push((a, length));
+    }
+
+    pub fn t(&self) -> f64 {
+        self.t
+    }
+
+    /// Ground sites the graph was built with.
+    pub fn sites(&self) -> &[GeoCoordinates] {
+        &self.ground[self.config.gateways.len()..]
+    }
+
+    fn first_site(&self) -> usize {
+        self.num_satellites + self.config.gateways.len()
+    }
+
+    fn edge_length(&self, a: usize, b: usize) -> f64 {
+        self.link_length(a, b).unwrap()
+    }
+
+    /// Id of the node corresponding to a ground site.
+    pub fn site_node(&self, site: usize) -> usize {
+        assert!(site < self.sites().len());
+
+        self.first_site() + site
+    }
+
+    /// Index of the ground site with the given coordinates, if the graph was
+    /// built with it.
+    pub fn site_index(&self, coordinates: &GeoCoordinates) -> Option<usize> {
+        self.sites().iter().position(|c| c == coordinates)
+    }
+
+    /// Length (in meters) of the link between two nodes, if it exists.
+    /// Terrestrial backhaul links count as the distance light travels during
+    /// their latency.
+    pub fn link_length(&self, a: usize, b: usize) -> Option<f64> {
+        self.adjacency[a].iter().find(|(n, _)| *n == b).map(|(_, length)| *length)
+    }
+
+    /// Id of the node corresponding to a gateway of the routing configuration.
+    pub fn gateway_node(&self, gateway: usize) -> usize {
+        assert!(gateway < self.config.gateways.len());
+
+        self.num_satellites + gateway
+    }
+
+    /// Every link of the graph (in both directions), with its length in meters.
+    pub fn links(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        self.adjacency.iter().enumerate().flat_map(|(a, links)| links.iter().map(move |&(b, length)| (a, b, length)))
+    }
+
+    /// Routing table towards a ground site: the next hop of every node along
+    /// its shortest path to the site (`None` if the site is unreachable).
+    pub fn next_hops(&self, to: usize) -> Vec<Option<usize>> {
+        self.shortest_paths(self.site_node(to)).predecessors.clone()
+    }
+
+    /// Routing table towards a gateway, like `next_hops`.
+    pub fn gateway_next_hops(&self, gateway: usize) -> Vec<Option<usize>> {
+        self.shortest_paths(self.gateway_node(gateway)).predecessors.clone()
+    }
+
+    /// Shortest paths from a node to every other node, calculated only once
+    /// between updates of the graph.
+    fn shortest_paths(&self, source: usize) -> Arc<ShortestPaths> {
+        if let Some(paths) = self.trees.lock().unwrap().get(&source) {
+            return paths.clone();
+        }
+
+        let paths = Arc::new(self.search(source, None, &Exclusions::default()));
+        self.trees.lock().unwrap().insert(source, paths.clone());
+
+        paths
+    }
+
+    /// Shortest path between two nodes, taken from the shortest path tree of
+    /// the source if it was already calculated, or otherwise from a search
+    /// that stops at the target.
+    fn shortest_path(&self, source: usize, target: usize) -> Option<Vec<usize>> {
+        let cached = self.trees.lock().unwrap().get(&source).cloned();
+
+        match cached {
+            Some(paths) => Self::path(&paths, target),
+            None => Self::path(&self.search(source, Some(target), &Exclusions::default()), target),
+        }
+    }
+
+    /// Finds the shortest paths from a node, stopping early once the target
+    /// node (if any) is reached. Ground sites other than the source never
+    /// relay traffic.
+    fn search(&self, source: usize, target: Option<usize>, exclusions: &Exclusions) -> ShortestPaths {
+        let num_nodes = self.positions.len();
+        let first_site = self.first_site();
+
+        // Backhaul links may be shorter than the straight line between their
+        // endpoints, so the distance heuristic is only admissible without gateways
+        let heuristic = |node: usize| match target {
+            Some(target) if self.config.gateways.is_empty() => self.positions[node].metric_distance(&self.positions[target]),
+            _ => 0.0,
+        };
+
+        let mut paths = ShortestPaths {
+            distances: vec![f64::INFINITY; num_nodes],
+            predecessors: vec![None; num_nodes],
+        };
+        let mut visited = vec![false; num_nodes];
+        let mut queue = BinaryHeap::new();
+
+        paths.distances[source] = 0.0;
+        queue.push(SearchState { estimate: heuristic(source), node: source });
+
+        while let Some(SearchState { node, .. }) = queue.pop() {
+            if visited[node] {
+                continue;
+            }
+            visited[node] = true;
+
+            if Some(node) == target {
+                break;
+            }
+            if node >= first_site && node != source {
+                continue;
+            }
+
+            for &(neighbor, length) in &self.adjacency[node] {
+                if exclusions.nodes.contains(&neighbor) || exclusions.excludes_link(node, neighbor) {
+                    continue;
+                }
+
+                let distance = paths.distances[node] + length;
+                if distance < paths.distances[neighbor] {
+                    paths.distances[neighbor] = distance;
+                    paths.predecessors[neighbor] = Some(node);
+                    queue.push(SearchState { estimate: distance + heuristic(neighbor), node: neighbor });
+                }
+            }
+        }
+
+        paths
+    }
+
+    /// Sequence of nodes in the shortest path to a target node, if reachable.
+    fn path(paths: &ShortestPaths, target: usize) -> Option<Vec<usize>> {
+        if paths.distances[target].is_infinite() {
+            return None;
+        }
+
+        let mut path = vec![target];
+        while let Some(predecessor) = paths.predecessors[*path.last().unwrap()] {
+            path.push(predecessor);
+        }
+        path.reverse();
+
+        Some(path)
+    }
+
+    fn path_length(&self, path: &[usize]) -> f64 {
+        path.windows(2).map(|w| self.edge_length(w[0], w[1])).sum()
+    }
+
+    /// Builds the route corresponding to a path between two ground sites.
+    fn build_route(&self, path: &[usize]) -> Route {
+        let source = path[0];
+        let target = path[path.len() - 1];
+
+        let route_node = |id: usize| match id {
+            _ if id < self.num_satellites => RouteNode::Satellite(id),
+            _ if id == source => RouteNode::Source,
+            _ if id == target => RouteNode::Destination,
+            _ => RouteNode::Gateway(id - self.num_satellites),
+        };
+
+        let nodes = path.iter().map(|&id| route_node(id)).collect();
+        let hops = path.windows(2).map(|w| {
+            let length = self.edge_length(w[0], w[1]);
+            let from = route_node(w[0]);
+            let to = route_node(w[1]);
+
+            match (from, to) {
+                (RouteNode::Gateway(g1), RouteNode::Gateway(g2)) => Hop {
+                    from,
+                    to,
+                    distance: self.config.gateways[g1].coordinates().haversine_distance(self.config.gateways[g2].coordinates()),
+                    delay: self.config.backhaul_latency,
+                },
+                _ => Hop { from, to, distance: length, delay: length / LIGHT_SPEED },
+            }
+        }).collect();
+
+        Route { length: self.path_length(path), nodes, hops }
+    }
+
+    fn site_nodes(&self, from: usize, to: usize) -> (usize, usize) {
+        (self.site_node(from), self.site_node(to))
+    }
+
+    /// Finds the shortest route between two ground sites (given by their
+    /// indices in the list of sites used to build the graph).
+    pub fn route(&self, from: usize, to: usize) -> Option<Route> {
+        let (source, target) = self.site_nodes(from, to);
+
+        self.shortest_path(source, target).map(|path| self.build_route(&path))
+    }
+
+    /// Finds up to `k` shortest loopless routes between two ground sites, in
+    /// increasing order of length (Yen's algorithm).
+    pub fn k_shortest_routes(&self, from: usize, to: usize, k: usize) -> Vec<Route> {
+        let (source, target) = self.site_nodes(from, to);
+
+        let mut shortest: Vec<Vec<usize>> = Vec::new();
+        let mut candidates: Vec<(f64, Vec<usize>)> = Vec::new();
+
+        if let Some(path) = self.shortest_path(source, target) {
+            shortest.push(path);
+        }
+
+        while !shortest.is_empty() && shortest.len() < k {
+            let previous = shortest.last().unwrap();
+
+            // Deviate from the previous path at each of its nodes
+            for i in 0..previous.len() - 1 {
+                let spur_node = previous[i];
+                let root = &previous[..=i];
+
+                let mut exclusions = Exclusions::default();
+                for path in shortest.iter().filter(|p| p.len() > i + 1 && p[..=i] == *root) {
+                    exclusions.exclude_link(path[i], path[i + 1]);
+                }
+                exclusions.nodes.extend(&root[..i]);
+
+                if let Some(spur_path) = Self::path(&self.search(spur_node, Some(target), &exclusions), target) {
+                    let path: Vec<usize> = root[..i].iter().chain(&spur_path).copied().collect();
+                    if !candidates.iter().any(|(_, p)| *p == path) {
+                        candidates.push((self.path_length(&path), path));
+                    }
+                }
+            }
+
+            let best = candidates.iter().enumerate()
+                .min_by(|(_, (l1, _)), (_, (l2, _))| l1.total_cmp(l2))
+                .map(|(i, _)| i);
+
+            match best {
+                Some(i) => shortest.push(candidates.swap_remove(i).1),
+                None => break,
+            }
+        }
+
+        shortest.iter().map(|path| self.build_route(path)).collect()
+    }
+
+    /// Finds the routes over which traffic between two ground sites is split,
+    /// according to the multipath mode of the routing configuration, along
+    /// with the fraction of the traffic carried by each route.
+    pub fn multipath_routes(&self, from: usize, to: usize) -> Vec<(Route, f64)> {
+        let routes = match self.config.multipath {
+            Multipath::ShortestPath => self.route(from, to).map(|r| (r, 1.0)).into_iter().collect(),
+            Multipath::Ecmp(tolerance) => self.ecmp_routes(from, to, tolerance),
+            Multipath::Weighted(k) => self.k_shortest_routes(from, to, k).into_iter()
+                .map(|r| {
+                    let weight = 1.0 / r.length().max(f64::EPSILON);
+                    (r, weight)
+                })
+                .collect(),
+        };
+
+        // Normalize fractions (traffic below the precision of equal-cost splits may be dropped)
+        let total: f64 = routes.iter().map(|(_, f)| f).sum();
+        routes.into_iter().map(|(r, f)| (r, f / total)).collect()
+    }
+
+    /// Splits the traffic between two ground sites over the links that are
+    /// part of a route whose length is within the tolerance of the shortest
+    /// route. Every node splits the traffic it receives evenly between its
+    /// next hops, and the resulting traffic of each link is decomposed into
+    /// routes.
+    fn ecmp_routes(&self, from: usize, to: usize, tolerance: f64) -> Vec<(Route, f64)> {
+        let (source, target) = self.site_nodes(from, to);
+        let first_site = self.first_site();
+
+        let to_target = &self.shortest_paths(target).distances;
+        let from_source = &self.shortest_paths(source).distances;
+        if to_target[source].is_infinite() {
+            return Vec::new();
+        }
+
+        // Next hops get strictly closer to the target, so routes are loopless
+        let max_length = to_target[source] * (1.0 + tolerance) + LENGTH_SLACK;
+        let next_hops = |node: usize| -> Vec<usize> {
+            self.adjacency[node].iter()
+                .filter(|&&(next, _)| next < first_site || next == target)
+                .filter(|&&(next, length)| {
+                    to_target[next] < to_target[node] && from_source[node] + length + to_target[next] <= max_length
+                })
+                .map(|&(next, _)| next)
+                .collect()
+        };
+
+        let mut nodes = vec![source];
+        let mut reached = vec![false; self.positions.len()];
+        reached[source] = true;
+        let mut i = 0;
+        while i < nodes.len() {
+            for next in next_hops(nodes[i]) {
+                if !reached[next] {
+                    reached[next] = true;
+                    nodes.push(next);
+                }
+            }
+            i += 1;
+        }
+
+        // Nodes only receive traffic from nodes farther from the target
+        nodes.sort_by(|a, b| to_target[*b].total_cmp(&to_target[*a]));
+
+        let mut received = vec![0.0; self.positions.len()];
+        let mut splits: Vec<Vec<(usize, f64)>> = vec![Vec::new(); self.positions.len()];
+        received[source] = 1.0;
+
+        for &node in nodes.iter().filter(|&&n| n != target) {
+            let hops = next_hops(node);
+            for &next in &hops {
+                let fraction = received[node] / hops.len() as f64;
+                splits[node].push((next, fraction));
+                received[next] += fraction;
+            }
+        }
+
+        // Follow links that still carry traffic from the source to the
+        // target, removing the traffic of the route from each of them
+        let mut routes = Vec::new();
+        while splits[source].iter().any(|&(_, f)| f > FRACTION_EPSILON) {
+            let mut path = vec![source];
+            let mut fraction = f64::INFINITY;
+
+            while let Some(&(next, f)) = splits[*path.last().unwrap()].iter().find(|(_, f)| *f > FRACTION_EPSILON) {
+                fraction = fraction.min(f);
+                path.push(next);
+            }
+            if *path.last().unwrap() != target {
+                break;
+            }
+
+            for w in path.windows(2) {
+                let split = splits[w[0]].iter_mut().find(|(next, _)| *next == w[1]).unwrap();
+                split.1 -= fraction;
+            }
+            routes.push((self.build_route(&path), fraction));
+        }
+
+        routes
+    }
+
+    /// Finds a maximal set of disjoint routes between two (different) ground
+    /// sites, by repeatedly taking the shortest route that does not share any
+    /// resources with the routes found so far.
+    pub fn disjoint_routes(&self, from: usize, to: usize, disjointness: Disjointness) -> Vec<Route> {
+        assert_ne!(from, to);
+        let (source, target) = self.site_nodes(from, to);
+
+        let mut exclusions = Exclusions::default();
+        let mut routes = Vec::new();
+
+        while let Some(path) = Self::path(&self.search(source, Some(target), &exclusions), target) {
+            match disjointness {
+                Disjointness::Link => path.windows(2).for_each(|w| exclusions.exclude_link(w[0], w[1])),
+                Disjointness::Node => exclusions.nodes.extend(&path[1..path.len() - 1]),
+            }
+
+            routes.push(self.build_route(&path));
+        }
+
+        routes
+    }
+
+    /// Calculates the round trip time (in seconds) between every pair of the
+    /// given ground sites, running a single shortest path search from each site.
+    pub fn rtt_matrix(&self, sites: &[usize]) -> Vec<Vec<Option<f64>>> {
+        let nodes: Vec<usize> = sites.iter().map(|&s| self.site_node(s)).collect();
+
+        nodes.par_iter().map(|&source| {
+            let paths = self.shortest_paths(source);
+
+            nodes.iter().map(|&target| match target {
+                _ if target == source => Some(0.0),
+                _ => Self::path(&paths, target).map(|path| self.build_route(&path).rtt()),
+            }).collect()
+        }).collect()
+    }
+}
+
+/// Finds the shortest route between two locations through a given topology
+/// and the gateways of the routing configuration.
+pub fn shortest_route(model: &Model, topology: &ConnectionGraph, config: &RoutingConfig,
+        c1: &GeoCoordinates, c2: &GeoCoordinates) -> Option<Route> {
+    let graph = RoutingGraph::new(model, topology, config, &[*c1, *c2]);
+    let (source, target) = graph.site_nodes(0, 1);
+
+    // A single query is faster when the search stops at the target
+    let paths = graph.search(source, Some(target), &Exclusions::default());
+    RoutingGraph::path(&paths, target).map(|path| graph.build_route(&path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{connection_strategy::{ConnectionStrategy, GridStrategy}, model::{ConstellationType, EARTH_RADIUS}};
+
+    fn routing_graph(multipath: Multipath) -> RoutingGraph {
+        let model = Model::new(6, 8, 53f64.to_radians(), ConstellationType::Delta, 1, EARTH_RADIUS + 4e6, 4);
+        let topology = GridStrategy::new(1, None, true).run(&model);
+        let sites = [GeoCoordinates::new(10.0, 20.0), GeoCoordinates::new(-30.0, 150.0)];
+
+        RoutingGraph::new(&model, &topology, &RoutingConfig::default().with_multipath(multipath), &sites)
+    }
+
+    /// Lengths of the `k` shortest loopless paths between two ground sites
+    /// relayed only by satellites, found by enumerating every path that could
+    /// still be shorter than the `k`-th best one.
+    fn shortest_path_lengths(graph: &RoutingGraph, from: usize, to: usize, k: usize) -> Vec<f64> {
+        fn visit(graph: &RoutingGraph, node: usize, target: usize, length: f64, k: usize, visited: &mut [bool], lengths: &mut Vec<f64>) {
+            let bound = if lengths.len() < k { f64::INFINITY } else { lengths[k - 1] };
+            if length + graph.positions[node].metric_distance(&graph.positions[target]) > bound {
+                return;
+            }
+
+            for &(next, link) in &graph.adjacency[node] {
+                if next == target {
+                    let i = lengths.partition_point(|&l| l < length + link);
+                    lengths.insert(i, length + link);
+                    lengths.truncate(k);
+                } else if next < graph.num_satellites && !visited[next] {
+                    visited[next] = true;
+                    visit(graph, next, target, length + link, k, visited, lengths);
+                    visited[next] = false;
+                }
+            }
+        }
+
+        let mut visited = vec![false; graph.num_satellites];
+        let mut lengths = Vec::new();
+        visit(graph, graph.site_node(from), graph.site_node(to), 0.0, k, &mut visited, &mut lengths);
+
+        lengths
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() <= 1e-9 * b, "{a} != {b}");
+    }
+
+    #[test]
+    fn route_is_shortest_path() {
+        let graph = routing_graph(Multipath::ShortestPath);
+        let lengths = shortest_path_lengths(&graph, 0, 1, 1);
+        assert_eq!(lengths.len(), 1);
+
+        let route = graph.route(0, 1).unwrap();
+        assert_close(route.length(), lengths[0]);
+        assert_eq!(route.satellites().count(), route.hop_count() - 1);
+    }
+
+    #[test]
+    fn k_shortest_routes_match_brute_force() {
+        let graph = routing_graph(Multipath::ShortestPath);
+        let lengths = shortest_path_lengths(&graph, 0, 1, 8);
+        assert_eq!(lengths.len(), 8);
+
+        let routes = graph.k_shortest_routes(0, 1, 8);
+        assert_eq!(routes.len(), 8);
+        for (route, length) in routes.iter().zip(&lengths) {
+            assert_close(route.length(), *length);
+            assert_eq!(route.satellites().collect::<HashSet<_>>().len(), route.hop_count() - 1);
+        }
+    }
+
+    #[test]
+    fn ecmp_splits_traffic_within_tolerance() {
+        let shortest = routing_graph(Multipath::ShortestPath).route(0, 1).unwrap().length();
+
+        for tolerance in [0.0, 0.2, 0.5] {
+            let routes = routing_graph(Multipath::Ecmp(tolerance)).multipath_routes(0, 1);
+            assert!(!routes.is_empty());
+            assert_close(routes.iter().map(|(_, f)| f).sum(), 1.0);
+
+            for (route, fraction) in &routes {
+                assert!(*fraction > 0.0);
+                assert!(route.length() >= shortest - LENGTH_SLACK && route.length() <= shortest * (1.0 + tolerance) + LENGTH_SLACK);
+            }
+        }
+
+        // A larger tolerance spreads the traffic over more routes
+        let count = |tolerance| routing_graph(Multipath::Ecmp(tolerance)).multipath_routes(0, 1).len();
+        assert!(count(0.5) > count(0.0));
+    }
+}
@@ -4,7 +4,7 @@ use json::{JsonValue, object};
 use crate::model::Simulation;
 
 pub fn init_msg(sim: &Simulation) -> String {
-    let first_plane = sim.orbital_planes().get(0);
+    let first_plane = sim.orbital_planes().first();
 
     let semimajor_axis = first_plane.map(|p| p.semimajor_axis()).unwrap_or(0.0);
     let inclination = first_plane.map(|p| p.inclination()).unwrap_or(0.0);
@@ -1,8 +1,9 @@
 
 use json::object;
 use petgraph::algo::connected_components;
+use rayon::prelude::*;
 
-use crate::model::{ConnectionGraph, Simulation, GeoCoordinates};
+use crate::{model::{ConnectionGraph, Simulation, GeoCoordinates}, routing::{Route, RouteNode}};
 
 fn round(x: f64, decimal_places: u32) -> f64 {
     let y = 10f64.powi(decimal_places as i32);
@@ -13,45 +14,193 @@ pub fn statistics_msg(sim: &Simulation) -> String {
     let edge_count = sim.topology().edge_count() as f64;
     let node_count = sim.topology().node_count() as f64;
 
+    let (links_added, links_removed) = sim.link_churn();
+
     let num_satellites = sim.satellites().len();
     let failed_satellites = sim.satellites().iter().filter(|s| !s.status()).count();
     let failure_ratio = failed_satellites as f64 / num_satellites as f64 * 100.0;
 
-    let london       = GeoCoordinates::new(51.507222, -0.1275);
-    let nyc          = GeoCoordinates::new(40.712778, -74.006111);
-    let johannesburg = GeoCoordinates::new(-26.204444, 28.045556);
-    let singapore    = GeoCoordinates::new(1.291667, 103.85);
-
-    let rtt_london_nyc          = sim.calc_rtt(&london, &nyc         );
-    let rtt_london_singapore    = sim.calc_rtt(&london, &singapore   );
-    let rtt_london_johannesburg = sim.calc_rtt(&london, &johannesburg);
-    let dist_london_nyc          = GeoCoordinates::haversine_distance(&london, &nyc         );
-    let dist_london_singapore    = GeoCoordinates::haversine_distance(&london, &singapore   );
-    let dist_london_johannesburg = GeoCoordinates::haversine_distance(&london, &johannesburg);
+    let critical = critical_elements(num_satellites, sim.topology());
+    let mut bridge_links: Vec<Vec<usize>> = critical.bridges.iter().map(|&(a, b)| vec![a.min(b), a.max(b)]).collect();
+    bridge_links.sort_unstable();
 
-    let obj = object! {
+    let mut obj = object! {
         t: round(sim.t(), 3),
         connected_components: connected_components(sim.topology()),
-        articulation_points: count_articulation_points(sim.satellites().len(), sim.topology()),
+        articulation_points: critical.articulation_points.len(),
+        bridges: critical.bridges.len(),
+        bridge_links: bridge_links,
         graph_density: 2.0 * edge_count / (node_count * (node_count - 1.0)),
         active_connections: edge_count,
+        links_added: links_added,
+        links_removed: links_removed,
         failure_ratio: failure_ratio,
-        rtt_nyc         : rtt_london_nyc.map(|rtt| rtt * 1e3),
-        rtt_singapore   : rtt_london_singapore.map(|rtt| rtt * 1e3),
-        rtt_johannesburg: rtt_london_johannesburg.map(|rtt| rtt * 1e3),
-        latency_nyc         : rtt_london_nyc.map(|rtt| rtt / dist_london_nyc),
-        latency_singapore   : rtt_london_singapore.map(|rtt| rtt / dist_london_singapore),
-        latency_johannesburg: rtt_london_johannesburg.map(|rtt| rtt / dist_london_johannesburg),
     };
 
+    for ((pair, route), history) in sim.city_pairs().iter().zip(sim.city_pair_routes()).zip(sim.path_histories()) {
+        let rtt = route.as_ref().map(|r| r.rtt());
+        let distance = GeoCoordinates::haversine_distance(pair.source(), pair.destination());
+
+        let _ = obj.insert(&format!("rtt_{}", pair.name()), rtt.map(|rtt| rtt * 1e3));
+        let _ = obj.insert(&format!("latency_{}", pair.name()), rtt.map(|rtt| rtt / distance));
+        let _ = obj.insert(&format!("ground_relay_{}", pair.name()), route.as_ref().map(|r| r.uses_ground_relay()));
+        let _ = obj.insert(&format!("hops_{}", pair.name()), route.as_ref().map(|r| r.hop_count()));
+        let _ = obj.insert(&format!("path_{}", pair.name()), route.as_ref().map(|r| r.satellites().collect::<Vec<_>>()));
+
+        let change = history.last_change();
+        let _ = obj.insert(&format!("path_changed_{}", pair.name()), change.is_some());
+        let _ = obj.insert(&format!("path_changes_{}", pair.name()), history.changes());
+        let _ = obj.insert(&format!("route_rtt_jump_{}", pair.name()), change.and_then(|c| c.rtt_jump).map(|jump| jump * 1e3));
+        let _ = obj.insert(&format!("path_lifetime_{}", pair.name()), change.and_then(|c| c.lifetime));
+        let _ = obj.insert(&format!("path_lifetime_mean_{}", pair.name()), history.mean_lifetime());
+        let _ = obj.insert(&format!("path_lifetime_median_{}", pair.name()), history.median_lifetime());
+    }
+
+    if let Some(routes) = sim.contact_routes() {
+        for (pair, route) in sim.city_pairs().iter().zip(routes) {
+            let _ = obj.insert(&format!("dtn_delay_{}", pair.name()), route.as_ref().map(|r| r.delay() * 1e3));
+            let _ = obj.insert(&format!("dtn_hops_{}", pair.name()), route.as_ref().map(|r| r.hops().len()));
+        }
+    }
+
+    let network = sim.network();
+    let max_flows: Vec<f64> = sim.region_pairs().par_iter().map(|p| network.max_flow(p.sources(), p.destinations())).collect();
+    for (pair, max_flow) in sim.region_pairs().iter().zip(max_flows) {
+        let _ = obj.insert(&format!("max_flow_{}", pair.name()), max_flow);
+    }
+
+    if !sim.traffic_demands().is_empty() {
+        let flows = sim.flow_assignment();
+        let bottlenecks: Vec<Vec<usize>> = flows.bottlenecks().into_iter().map(|(a, b)| vec![a, b]).collect();
+
+        let _ = obj.insert("max_link_utilization", flows.max_utilization());
+        let _ = obj.insert("demand_satisfied", flows.satisfied_fraction());
+        let _ = obj.insert("bottleneck_links", bottlenecks);
+        let _ = obj.insert("path_diversity", flows.path_diversity());
+        let _ = obj.insert("link_load_max", flows.link_loads().map(|(_, l)| l).fold(0.0, f64::max));
+        let _ = obj.insert("link_load_fairness", flows.load_fairness());
+        let _ = obj.insert("satellite_betweenness", flows.satellite_betweenness().into_iter().map(|b| round(b, 4)).collect::<Vec<_>>());
+    }
+
+    if let Some(link_state) = sim.link_state() {
+        let _ = obj.insert("routing_convergence_time", link_state.convergence_time().map(|t| t * 1e3));
+        let _ = obj.insert("stale_routing_tables", link_state.stale_tables());
+        let _ = obj.insert("stale_routes", link_state.stale_routes());
+        let _ = obj.insert("stale_route_window", link_state.last_stale_window().map(|t| t * 1e3));
+    }
+
+    if let Some(packets) = sim.packet_statistics() {
+        let _ = obj.insert("packets_generated", packets.generated);
+        let _ = obj.insert("packets_delivered", packets.delivered);
+        let _ = obj.insert("packets_dropped", packets.dropped());
+        let _ = obj.insert("packets_dropped_queue", packets.dropped_queue);
+        let _ = obj.insert("packets_dropped_no_route", packets.dropped_no_route);
+        let _ = obj.insert("packets_dropped_link_down", packets.dropped_link_down);
+        let _ = obj.insert("packet_delay_mean", packets.mean_delay().map(|d| d * 1e3));
+        for p in [50, 95, 99, 100] {
+            let _ = obj.insert(&format!("packet_delay_p{}", p), packets.delay_percentile(p as f64).map(|d| d * 1e3));
+        }
+    }
+
+    if !sim.ground_sites().is_empty() {
+        let matrix: Vec<Vec<Option<f64>>> = sim.rtt_matrix().into_iter()
+            .map(|row| row.into_iter().map(|rtt| rtt.map(|rtt| rtt * 1e3)).collect())
+            .collect();
+        let _ = obj.insert("site_rtt_matrix", matrix);
+    }
+
     obj.dump()
 }
 
+/// Change of the route between a city pair.
+#[derive(Clone, Copy)]
+pub struct PathChange {
+    /// Time (in seconds) during which the previous route was used, if the
+    /// city pair was reachable.
+    pub lifetime: Option<f64>,
+    /// Difference (in seconds) between the new and the previous round trip
+    /// times, if the city pair was and still is reachable.
+    pub rtt_jump: Option<f64>,
+}
+
+/// Routes used between a city pair over successive connection updates.
+#[derive(Default)]
+pub struct PathHistory {
+    started: bool,
+    path: Option<Vec<RouteNode>>,
+    rtt: Option<f64>,
+    since: f64,
+    changes: usize,
+    lifetimes: Vec<f64>,
+    last_change: Option<PathChange>,
+}
+
+impl PathHistory {
+    /// Records the route used at time `t` (`None` if unreachable).
+    pub fn update(&mut self, route: Option<&Route>, t: f64) {
+        let path = route.map(|r| r.nodes().to_vec());
+        let rtt = route.map(Route::rtt);
+
+        self.last_change = None;
+        if self.started && path != self.path {
+            let lifetime = self.path.as_ref().map(|_| t - self.since);
+            let rtt_jump = self.rtt.zip(rtt).map(|(previous, current)| current - previous);
+
+            self.lifetimes.extend(lifetime);
+            self.changes += 1;
+            self.last_change = Some(PathChange { lifetime, rtt_jump });
+        }
+
+        if !self.started || path != self.path {
+            self.since = t;
+        }
+        self.started = true;
+        self.path = path;
+        self.rtt = rtt;
+    }
+
+    /// Change of route at the most recent update, if any.
+    pub fn last_change(&self) -> Option<PathChange> {
+        self.last_change
+    }
+
+    pub fn changes(&self) -> usize {
+        self.changes
+    }
+
+    /// Time (in seconds) during which each of the previous routes was used.
+    pub fn lifetimes(&self) -> &[f64] {
+        &self.lifetimes
+    }
+
+    pub fn mean_lifetime(&self) -> Option<f64> {
+        match self.lifetimes.len() {
+            0 => None,
+            n => Some(self.lifetimes.iter().sum::<f64>() / n as f64),
+        }
+    }
+
+    pub fn median_lifetime(&self) -> Option<f64> {
+        let mut lifetimes = self.lifetimes.clone();
+        lifetimes.sort_unstable_by(f64::total_cmp);
+
+        match lifetimes.len() {
+            0 => None,
+            n if n % 2 == 0 => Some((lifetimes[n / 2 - 1] + lifetimes[n / 2]) / 2.0),
+            n => Some(lifetimes[n / 2]),
+        }
+    }
+}
+
 struct TarjanInformation {
     visited: Vec<bool>,
     depth: Vec<u32>,
     low: Vec<u32>,
     parent: Vec<Option<usize>>,
+    children: Vec<usize>,
+    /// Whether a child of the node cannot reach any of its ancestors
+    /// without going through it.
+    separates_child: Vec<bool>,
 }
 
 impl TarjanInformation {
@@ -61,59 +210,130 @@ impl TarjanInformation {
             depth: vec![0; node_count],
             low: vec![0; node_count],
             parent: vec![None; node_count],
+            children: vec![0; node_count],
+            separates_child: vec![false; node_count],
         }
     }
+
+    fn visit(&mut self, idx: usize, d: u32) {
+        self.visited[idx] = true;
+        self.depth[idx] = d;
+        self.low[idx] = d;
+    }
 }
 
-fn count_articulation_points(num_satellites: usize, g: &ConnectionGraph) -> usize {
-    let mut articulation_points = 0;
+/// Articulation points (satellites whose failure would split their connected
+/// component of the topology) and bridges (links whose failure would split
+/// their component).
+pub struct CriticalElements {
+    pub articulation_points: Vec<usize>,
+    pub bridges: Vec<(usize, usize)>,
+}
 
-    if let Some(root) = g.nodes().next() {
-        let mut info = TarjanInformation::new(num_satellites);
+/// Finds the articulation points and bridges of every connected component of
+/// the topology, using Tarjan's algorithm with an explicit stack so that
+/// large constellations do not overflow the call stack.
+pub fn critical_elements(num_satellites: usize, g: &ConnectionGraph) -> CriticalElements {
+    let mut critical = CriticalElements {
+        articulation_points: Vec::new(),
+        bridges: Vec::new(),
+    };
+
+    let mut info = TarjanInformation::new(num_satellites);
+
+    for root in g.nodes() {
+        if info.visited[root] {
+            continue;
+        }
 
-        fn dfs(g: &ConnectionGraph, info: &mut TarjanInformation,
-                articulation_points: &mut usize, idx: usize, d: u32) {
-            info.visited[idx] = true;
-            info.depth[idx] = d;
-            info.low[idx] = d;
+        info.visit(root, 0);
+        let mut stack = vec![(root, g.neighbors(root))];
 
-            let mut children = 0;
-            let mut is_articulation = false;
+        while let Some((idx, neighbors)) = stack.last_mut() {
+            let idx = *idx;
 
-            for n_idx in g.neighbors(idx) {
-                if !info.visited[n_idx] {
+            match neighbors.next() {
+                Some(n_idx) if !info.visited[n_idx] => {
                     info.parent[n_idx] = Some(idx);
-                    dfs(g, info, articulation_points, n_idx, d + 1);
-                    children += 1;
-                    if info.low[n_idx] >= info.depth[idx] {
-                        is_articulation = true;
+                    info.children[idx] += 1;
+                    info.visit(n_idx, info.depth[idx] + 1);
+                    stack.push((n_idx, g.neighbors(n_idx)));
+                },
+                Some(n_idx) => {
+                    if info.parent[idx] != Some(n_idx) {
+                        info.low[idx] = u32::min(info.low[idx], info.depth[n_idx]);
                     }
-                    info.low[idx] = u32::min(info.low[idx], info.low[n_idx]);
-                }
-                else {
-                    let not_parent = match info.parent[idx] {
-                        None => true,
-                        Some(p_idx) => n_idx != p_idx,
+                },
+                None => {
+                    stack.pop();
+
+                    let is_articulation = match info.parent[idx] {
+                        None => info.children[idx] > 1,
+                        Some(_) => info.separates_child[idx],
                     };
+                    if is_articulation {
+                        critical.articulation_points.push(idx);
+                    }
 
-                    if not_parent {
-                        info.low[idx] = u32::min(info.low[idx], info.depth[n_idx]);
+                    // Every descendant of the node has been explored, so it can update its parent
+                    if let Some(p_idx) = info.parent[idx] {
+                        if info.low[idx] >= info.depth[p_idx] {
+                            info.separates_child[p_idx] = true;
+                        }
+                        if info.low[idx] > info.depth[p_idx] {
+                            critical.bridges.push((p_idx, idx));
+                        }
+                        info.low[p_idx] = u32::min(info.low[p_idx], info.low[idx]);
                     }
-                }
+                },
             }
+        }
+    }
+
+    critical
+}
 
-            is_articulation = match info.parent[idx] {
-                None => children > 1,
-                Some(_) => is_articulation,
-            };
+#[cfg(test)]
+mod tests {
+    use petgraph::graphmap::GraphMap;
 
-            if is_articulation {
-                *articulation_points += 1;
-            }
+    use super::*;
+    use crate::model::Link;
+
+    fn graph(edges: &[(usize, usize)]) -> ConnectionGraph {
+        let mut g = GraphMap::new();
+        for &(a, b) in edges {
+            g.add_edge(a, b, Link { length: 1.0, capacity: 1.0 });
         }
+        g
+    }
+
+    /// Articulation points and bridges (smallest id first) in increasing order.
+    fn sorted(mut critical: CriticalElements) -> (Vec<usize>, Vec<(usize, usize)>) {
+        critical.articulation_points.sort_unstable();
+        let mut bridges: Vec<(usize, usize)> = critical.bridges.iter().map(|&(a, b)| (a.min(b), a.max(b))).collect();
+        bridges.sort_unstable();
+
+        (critical.articulation_points, bridges)
+    }
+
+    #[test]
+    fn critical_elements_of_every_component() {
+        // Two triangles joined by a bridge, and two triangles sharing a satellite
+        let g = graph(&[(0, 1), (1, 2), (2, 0), (2, 3), (3, 4), (4, 5), (5, 3), (6, 7), (7, 8), (8, 6), (8, 9), (9, 10), (10, 8)]);
 
-        dfs(g, &mut info, &mut articulation_points, root, 0);
+        let (articulation_points, bridges) = sorted(critical_elements(11, &g));
+        assert_eq!(articulation_points, vec![2, 3, 8]);
+        assert_eq!(bridges, vec![(2, 3)]);
     }
 
-    articulation_points
+    #[test]
+    fn isolated_satellites_and_paths() {
+        let mut g = graph(&[(0, 1), (1, 2)]);
+        g.add_node(4);
+
+        let (articulation_points, bridges) = sorted(critical_elements(5, &g));
+        assert_eq!(articulation_points, vec![1]);
+        assert_eq!(bridges, vec![(0, 1), (1, 2)]);
+    }
 }
@@ -0,0 +1,315 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use toml::{Value, map::Map};
+
+use crate::{
+    connection_strategy::{
+        ConnectionStrategy, GridStrategy, NearestNeighborStrategy, HysteresisStrategy, MatchingStrategy,
+        MatchingWeight, PredictiveStrategy, MotifStrategy, MotifSearch, MotifObjective, KConnectedStrategy,
+        TrafficAwareStrategy, TrafficObjective, HybridStrategy, HybridComponent,
+    },
+    model::{CityPair, TrafficDemand},
+};
+
+/// Parameters of a strategy, as specified in a `[strategy]` table.
+pub type StrategyParameters = Map<String, Value>;
+
+/// Parts of the simulation configuration (outside of the `[strategy]` table)
+/// that strategies may depend on.
+pub struct StrategyConfig<'a> {
+    pub city_pairs: &'a [CityPair],
+    pub traffic_demands: &'a [TrafficDemand],
+    pub rng_seed: Option<u64>,
+}
+
+pub type StrategyConstructor = Arc<dyn Fn(&StrategyParameters, &StrategyConfig, &StrategyRegistry)
+    -> Result<Box<dyn ConnectionStrategy>, String> + Send + Sync>;
+
+/// Maps strategy names (the `type` parameter of a `[strategy]` table) to
+/// functions that build the corresponding strategy. Constructors receive the
+/// registry itself so that composite strategies can build their components.
+#[derive(Clone)]
+pub struct StrategyRegistry {
+    constructors: BTreeMap<String, StrategyConstructor>,
+}
+
+impl StrategyRegistry {
+    /// Creates a registry without any strategies.
+    pub fn new() -> Self {
+        StrategyRegistry {
+            constructors: BTreeMap::new(),
+        }
+    }
+
+    /// Creates a registry with all of the built-in strategies.
+    pub fn with_builtin_strategies() -> Self {
+        let mut registry = Self::new();
+
+        registry.register("grid", grid);
+        registry.register("nearest_neighbor", nearest_neighbor);
+        registry.register("hysteresis", hysteresis);
+        registry.register("matching", matching);
+        registry.register("predictive", predictive);
+        registry.register("motif", motif);
+        registry.register("k_connected", k_connected);
+        registry.register("traffic_aware", traffic_aware);
+        registry.register("hybrid", hybrid);
+
+        registry
+    }
+
+    /// Registers a strategy constructor, replacing any previous constructor
+    /// with the same name.
+    pub fn register<F>(&mut self, name: &str, constructor: F)
+    where
+        F: Fn(&StrategyParameters, &StrategyConfig, &StrategyRegistry) -> Result<Box<dyn ConnectionStrategy>, String> + Send + Sync + 'static,
+    {
+        self.constructors.insert(name.to_owned(), Arc::new(constructor));
+    }
+
+    /// Names of the registered strategies, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.constructors.keys().map(String::as_str)
+    }
+
+    /// Builds the strategy described by a `[strategy]` table. The `type`
+    /// parameter defaults to `"grid"`.
+    pub fn build(&self, params: &StrategyParameters, config: &StrategyConfig) -> Result<Box<dyn ConnectionStrategy>, String> {
+        let name = match params.get("type") {
+            Some(Value::String(name)) => name.as_str(),
+            Some(_) => return Err("Strategy type must be a string.".to_owned()),
+            None => "grid",
+        };
+
+        match self.constructors.get(name) {
+            Some(constructor) => constructor(params, config, self),
+            None => Err(format!(
+                "Invalid strategy type \"{}\". Available strategies: {}.",
+                name, self.names().collect::<Vec<_>>().join(", "),
+            )),
+        }
+    }
+}
+
+impl Default for StrategyRegistry {
+    fn default() -> Self {
+        Self::with_builtin_strategies()
+    }
+}
+
+fn grid(params: &StrategyParameters, _: &StrategyConfig, _: &StrategyRegistry) -> Result<Box<dyn ConnectionStrategy>, String> {
+    let offset                   = params.get("offset")                  .and_then(Value::as_integer).unwrap_or(0) as usize;
+    let max_cross_plane_latitude = params.get("max_cross_plane_latitude").and_then(Value::as_float);
+    let seam_links               = params.get("seam_links")              .and_then(Value::as_bool)   .unwrap_or(true);
+
+    if max_cross_plane_latitude.is_some_and(|latitude| !(0.0..=90.0).contains(&latitude)) {
+        return Err("Maximum cross-plane latitude must be in the interval [0, 90].".to_owned());
+    }
+
+    Ok(Box::new(GridStrategy::new(offset, max_cross_plane_latitude, seam_links)))
+}
+
+fn nearest_neighbor(params: &StrategyParameters, _: &StrategyConfig, _: &StrategyRegistry) -> Result<Box<dyn ConnectionStrategy>, String> {
+    if params.get("cross_plane_only").and_then(Value::as_bool).unwrap_or(false) {
+        Ok(Box::new(NearestNeighborStrategy::cross_plane()))
+    } else {
+        Ok(Box::new(NearestNeighborStrategy::new()))
+    }
+}
+
+fn hysteresis(params: &StrategyParameters, _: &StrategyConfig, _: &StrategyRegistry) -> Result<Box<dyn ConnectionStrategy>, String> {
+    let margin = params.get("margin").and_then(Value::as_float).unwrap_or(0.1);
+    if !(0.0..1.0).contains(&margin) {
+        return Err("Hysteresis margin must be in the interval [0, 1[.".to_owned());
+    }
+
+    Ok(Box::new(HysteresisStrategy::new(margin)))
+}
+
+fn matching(params: &StrategyParameters, _: &StrategyConfig, _: &StrategyRegistry) -> Result<Box<dyn ConnectionStrategy>, String> {
+    let candidates = params.get("candidates").and_then(Value::as_integer).unwrap_or(8);
+    let horizon    = params.get("horizon")   .and_then(Value::as_float)  .unwrap_or(600.0);
+
+    if candidates <= 0 {
+        return Err("Number of candidates must be positive.".to_owned());
+    }
+    if horizon.is_nan() || horizon <= 0.0 {
+        return Err("Horizon must be positive.".to_owned());
+    }
+
+    let weight = match params.get("weight").and_then(Value::as_str).unwrap_or("inverse_distance") {
+        "inverse_distance" => MatchingWeight::InverseDistance,
+        "lifetime" => MatchingWeight::Lifetime(horizon),
+        other => return Err(format!("Invalid matching weight \"{}\".", other)),
+    };
+
+    Ok(Box::new(MatchingStrategy::new(weight, candidates as usize)))
+}
+
+fn predictive(params: &StrategyParameters, _: &StrategyConfig, _: &StrategyRegistry) -> Result<Box<dyn ConnectionStrategy>, String> {
+    let candidates = params.get("candidates").and_then(Value::as_integer).unwrap_or(8);
+    let horizon    = params.get("horizon")   .and_then(Value::as_float)  .unwrap_or(600.0);
+
+    if candidates <= 0 {
+        return Err("Number of candidates must be positive.".to_owned());
+    }
+    if horizon.is_nan() || horizon <= 0.0 {
+        return Err("Horizon must be positive.".to_owned());
+    }
+
+    Ok(Box::new(PredictiveStrategy::new(horizon, candidates as usize)))
+}
+
+fn motif(params: &StrategyParameters, config: &StrategyConfig, _: &StrategyRegistry) -> Result<Box<dyn ConnectionStrategy>, String> {
+    let motif = match params.get("motif").and_then(Value::as_array) {
+        Some(links) => links.iter().map(|link| match link.as_array().map(Vec::as_slice) {
+            Some([plane_offset, slot_offset]) => match (plane_offset.as_integer(), slot_offset.as_integer()) {
+                (Some(0), Some(0)) => Err("Motif links cannot have zero plane and slot offsets.".to_owned()),
+                (Some(plane_offset), Some(slot_offset)) if plane_offset >= 0 => Ok((plane_offset as usize, slot_offset)),
+                _ => Err("Motif offsets must be integers (with non-negative plane offsets).".to_owned()),
+            },
+            _ => Err("Motif links must be [plane_offset, slot_offset] arrays.".to_owned()),
+        }).collect::<Result<Vec<_>, _>>()?,
+        None => vec![(0, 1), (1, 0)],
+    };
+
+    let search = if params.get("search").and_then(Value::as_bool).unwrap_or(false) {
+        let max_plane_offset = params.get("max_plane_offset").and_then(Value::as_integer).unwrap_or(2);
+        let max_slot_offset  = params.get("max_slot_offset") .and_then(Value::as_integer).unwrap_or(2);
+        if max_plane_offset < 0 || max_slot_offset < 0 {
+            return Err("Maximum motif offsets must be non-negative.".to_owned());
+        }
+
+        let objective = match params.get("objective").and_then(Value::as_str).unwrap_or("hops") {
+            "hops" => MotifObjective::Hops,
+            "rtt" => MotifObjective::Rtt,
+            other => return Err(format!("Invalid motif objective \"{}\".", other)),
+        };
+        Some(MotifSearch::new(max_plane_offset as usize, max_slot_offset as usize, objective, config.city_pairs.to_vec()))
+    } else {
+        None
+    };
+
+    Ok(Box::new(MotifStrategy::new(motif, search)))
+}
+
+/// Builds the strategy whose topology is modified by strategies that augment
+/// or optimize another strategy's result. The `base` parameter is either the
+/// name of a strategy (which receives the same parameters as the enclosing
+/// strategy) or a table describing it.
+fn base_strategy(params: &StrategyParameters, config: &StrategyConfig, registry: &StrategyRegistry) -> Result<Box<dyn ConnectionStrategy>, String> {
+    match params.get("base") {
+        Some(Value::Table(base)) => registry.build(base, config),
+        Some(Value::String(name)) => {
+            let mut base = params.clone();
+            base.insert("type".to_owned(), Value::String(name.clone()));
+            base.remove("base");
+            registry.build(&base, config)
+        },
+        Some(_) => Err("Base strategy must be a strategy name or a table.".to_owned()),
+        None => {
+            let mut base = params.clone();
+            base.insert("type".to_owned(), Value::String("grid".to_owned()));
+            registry.build(&base, config)
+        },
+    }
+}
+
+fn k_connected(params: &StrategyParameters, config: &StrategyConfig, registry: &StrategyRegistry) -> Result<Box<dyn ConnectionStrategy>, String> {
+    let k = params.get("k").and_then(Value::as_integer).unwrap_or(2);
+    if k < 0 {
+        return Err("Target connectivity must be non-negative.".to_owned());
+    }
+
+    Ok(Box::new(KConnectedStrategy::new(base_strategy(params, config, registry)?, k as usize)))
+}
+
+fn traffic_aware(params: &StrategyParameters, config: &StrategyConfig, registry: &StrategyRegistry) -> Result<Box<dyn ConnectionStrategy>, String> {
+    let time_budget    = params.get("time_budget")   .and_then(Value::as_float)  .unwrap_or(1.0);
+    let max_iterations = params.get("max_iterations").and_then(Value::as_integer).unwrap_or(1000);
+
+    if !time_budget.is_finite() || time_budget <= 0.0 {
+        return Err("Time budget must be a positive number of seconds.".to_owned());
+    }
+    if max_iterations < 0 {
+        return Err("Maximum number of iterations must be non-negative.".to_owned());
+    }
+
+    let objective = match params.get("objective").and_then(Value::as_str).unwrap_or("latency") {
+        "latency" => TrafficObjective::Latency,
+        "throughput" => TrafficObjective::Throughput,
+        other => return Err(format!("Invalid traffic objective \"{}\".", other)),
+    };
+
+    Ok(Box::new(TrafficAwareStrategy::new(
+        base_strategy(params, config, registry)?,
+        config.traffic_demands.to_vec(),
+        objective,
+        time_budget,
+        max_iterations as usize,
+        config.rng_seed.unwrap_or(0),
+    )))
+}
+
+fn hybrid(params: &StrategyParameters, config: &StrategyConfig, registry: &StrategyRegistry) -> Result<Box<dyn ConnectionStrategy>, String> {
+    let components = params.get("components").and_then(Value::as_array)
+        .ok_or("Hybrid strategy components must be an array of tables.")?
+        .iter()
+        .map(|component| {
+            let component = component.as_table().ok_or("Hybrid strategy components must be an array of tables.")?;
+            let terminals = component.get("terminals").and_then(Value::as_integer)
+                .ok_or("Hybrid strategy components must specify their number of terminals.")?;
+            Ok(HybridComponent::new(registry.build(component, config)?, terminals as usize))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(Box::new(HybridStrategy::new(components)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(params: &str) -> Result<Box<dyn ConnectionStrategy>, String> {
+        let params: StrategyParameters = toml::from_str(params).unwrap();
+        let config = StrategyConfig { city_pairs: &[], traffic_demands: &[], rng_seed: None };
+
+        StrategyRegistry::default().build(&params, &config)
+    }
+
+    #[test]
+    fn matching_parameters_are_validated() {
+        assert!(build("type = \"matching\"").is_ok());
+        assert!(build("type = \"matching\"\ncandidates = 0").is_err());
+        assert!(build("type = \"matching\"\ncandidates = -1").is_err());
+        assert!(build("type = \"matching\"\nweight = \"lifetime\"\nhorizon = -600.0").is_err());
+    }
+
+    #[test]
+    fn predictive_parameters_are_validated() {
+        assert!(build("type = \"predictive\"").is_ok());
+        assert!(build("type = \"predictive\"\ncandidates = -8").is_err());
+        assert!(build("type = \"predictive\"\nhorizon = 0.0").is_err());
+    }
+
+    #[test]
+    fn motif_parameters_are_validated() {
+        assert!(build("type = \"motif\"\nmotif = [[0, 1], [1, -1]]").is_ok());
+        assert!(build("type = \"motif\"\nmotif = [[0, 0]]").is_err());
+        assert!(build("type = \"motif\"\nsearch = true\nmax_slot_offset = -1").is_err());
+    }
+
+    #[test]
+    fn k_connected_parameters_are_validated() {
+        assert!(build("type = \"k_connected\"\nk = 3").is_ok());
+        assert!(build("type = \"k_connected\"\nk = -1").is_err());
+    }
+
+    #[test]
+    fn traffic_aware_parameters_are_validated() {
+        assert!(build("type = \"traffic_aware\"").is_ok());
+        assert!(build("type = \"traffic_aware\"\ntime_budget = -1.0").is_err());
+        assert!(build("type = \"traffic_aware\"\ntime_budget = nan").is_err());
+        assert!(build("type = \"traffic_aware\"\nmax_iterations = -1").is_err());
+    }
+}